@@ -0,0 +1,243 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::hash::Hash;
+
+use crate::cell::Color;
+use crate::field::{self, get_top, CellSet, Field};
+use crate::nazopuyo_info::NazopuyoInfo;
+use crate::parallel_solver::expand;
+use crate::rules::Rules;
+use crate::solve_options::{Budget, SolveOptions, SolveResult};
+
+/// 盤面の評価関数 (大きいほど良い)
+/// 連鎖やつながりの判定は rules に合わせる
+pub trait Evaluator<F: Field>: Send + Sync {
+    fn evaluate(&self, field: &F, rules: &Rules) -> i32;
+}
+
+impl<F, T> Evaluator<F> for T
+where
+    F: Field,
+    T: Fn(&F, &Rules) -> i32 + Send + Sync,
+{
+    fn evaluate(&self, field: &F, rules: &Rules) -> i32 {
+        self(field, rules)
+    }
+}
+
+/// 潜在連鎖数
+/// どこかの列に同じ色を 1, 2 個足したときに起きる最大の連鎖数
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PotentialChain;
+
+impl<F: Field + Clone> Evaluator<F> for PotentialChain {
+    fn evaluate(&self, field: &F, rules: &Rules) -> i32 {
+        let mut best = 0;
+        for j in 0..F::WIDTH {
+            for color in Color::ALL {
+                let mut f = field.clone();
+                for _ in 0..2 {
+                    let y = match get_top(&f, j) {
+                        Some(0) => break,
+                        Some(y) => y - 1,
+                        None => F::HEIGHT - 1,
+                    };
+                    f.set(y, j, field::landing(rules, y, color.into()));
+                    if field::has_chain_with(&f, rules) {
+                        best = best.max(field::chain_with(&mut f, rules));
+                        break;
+                    }
+                }
+            }
+        }
+        best as i32
+    }
+}
+
+/// つながりの良さ
+/// 見える段の色ぷよのグループの大きさの二乗和
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Connectivity;
+
+impl<F: Field> Evaluator<F> for Connectivity {
+    fn evaluate(&self, field: &F, rules: &Rules) -> i32 {
+        let mut vis = CellSet::new::<F>();
+        let mut connected = vec![];
+        let mut score = 0;
+
+        for i in rules.hidden_rows..F::HEIGHT {
+            for j in 0..F::WIDTH {
+                if !vis.insert(i, j) || field.get(i, j).color().is_none() {
+                    continue;
+                }
+                field::flood(field, rules, (i, j), &mut vis, &mut connected);
+                score += (connected.len() * connected.len()) as i32;
+            }
+        }
+        score
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BeamResult<F> {
+    /// 条件を満たす盤面が見つかれば Solved
+    /// ビームサーチは全探索ではないので NoSolution は「見つからなかった」という意味
+    pub result: SolveResult<F>,
+    /// 最後まで置いた盤面のうち連鎖数が一番多かったものと、その連鎖数
+    pub best: Option<(F, u32)>,
+}
+
+/// 各深さで評価値の高い width 個だけを残して進む探索
+pub struct BeamSearch<E> {
+    pub width: usize,
+    pub evaluator: E,
+}
+
+impl<E> BeamSearch<E> {
+    pub fn new(width: usize, evaluator: E) -> Self {
+        Self { width, evaluator }
+    }
+
+    pub fn search<F>(&self, field: &F, info: &NazopuyoInfo, options: &SolveOptions) -> BeamResult<F>
+    where
+        F: Field + Clone + Eq + Hash,
+        E: Evaluator<F>,
+    {
+        let budget = Budget::new(options, info.next.len());
+        let mut best: Option<(F, u32)> = None;
+        let mut beam = vec![field.clone()];
+        let mut children = vec![];
+
+        for depth in 0..info.next.len() as u32 {
+            let last = depth + 1 == info.next.len() as u32;
+            let mut seen = HashSet::new();
+            let mut scored = vec![];
+
+            for f in beam.iter() {
+                if let Some(reason) = budget.visit(depth) {
+                    budget.finish();
+                    return BeamResult {
+                        result: SolveResult::GaveUp(reason),
+                        best,
+                    };
+                }
+                expand(&mut f.clone(), info, depth, &mut children);
+                for child in children.drain(..) {
+                    let hit = !seen.insert(child.clone());
+                    budget.lookup(hit);
+                    if hit {
+                        continue;
+                    }
+                    if last {
+                        let mut fired = child.clone();
+                        let chain = field::chain_with(&mut fired, &info.rules);
+                        if chain == info.chain {
+                            budget.finish();
+                            return BeamResult {
+                                result: SolveResult::Solved(child.clone()),
+                                best: Some((child, chain)),
+                            };
+                        }
+                        if best.as_ref().is_none_or(|(_, c)| *c < chain) {
+                            best = Some((child, chain));
+                        }
+                    } else {
+                        scored.push((self.evaluator.evaluate(&child, &info.rules), child));
+                    }
+                }
+            }
+
+            // 同点なら生成順 (列の優先順) を保つ
+            scored.sort_by_key(|s| Reverse(s.0));
+            beam = scored.into_iter().take(self.width).map(|(_, f)| f).collect();
+        }
+
+        budget.finish();
+        BeamResult {
+            result: SolveResult::NoSolution,
+            best,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cell::Cell;
+    use crate::field_naive_bit::FieldNaiveBit;
+    use crate::naive_next_puyo::NaiveNextPuyo;
+    use crate::solve_options::GiveUpReason;
+
+    fn puzzle() -> (FieldNaiveBit, NazopuyoInfo) {
+        let mut field = FieldNaiveBit::new();
+        field.set(12, 1, Cell::Ojama);
+        field.set(12, 2, Cell::RED);
+        field.set(12, 3, Cell::RED);
+        field.set(12, 4, Cell::RED);
+        field.set(12, 5, Cell::GREEN);
+        field.set(11, 1, Cell::Ojama);
+        field.set(11, 2, Cell::Ojama);
+        field.set(11, 3, Cell::Ojama);
+        field.set(11, 4, Cell::GREEN);
+        field.set(11, 5, Cell::GREEN);
+        field.set(10, 3, Cell::BLUE);
+        field.set(10, 4, Cell::BLUE);
+        let next = NaiveNextPuyo::from_vec(vec![[3, 4], [3, 2]]).unwrap();
+        (field, NazopuyoInfo::new(3, next))
+    }
+
+    #[test]
+    fn potential_chain() {
+        let mut field = FieldNaiveBit::new();
+        field.set(12, 0, Cell::RED);
+        field.set(11, 0, Cell::RED);
+        field.set(10, 0, Cell::RED);
+        field.set(9, 0, Cell::BLUE);
+        field.set(8, 0, Cell::RED);
+        field.set(12, 1, Cell::BLUE);
+        field.set(11, 1, Cell::BLUE);
+        field.set(10, 1, Cell::BLUE);
+        // 2 列目に青を置くと青が消えて赤が落ちる
+        assert_eq!(PotentialChain.evaluate(&field, &Rules::TSU), 2);
+        assert_eq!(Connectivity.evaluate(&field, &Rules::TSU), 9 + 1 + 1 + 9);
+    }
+
+    #[test]
+    fn evaluators_follow_rules() {
+        let mut field = FieldNaiveBit::new();
+        field.set(12, 0, Cell::RED);
+        field.set(11, 0, Cell::RED);
+        field.set(12, 1, Cell::BLUE);
+        field.set(11, 1, Cell::BLUE);
+        field.set(10, 1, Cell::RED);
+        // 3 列目に青を足すと青が消えて赤が落ちる。3 個で消えるルールならそこで赤も消える
+        assert_eq!(PotentialChain.evaluate(&field, &Rules::TSU), 1);
+        assert_eq!(PotentialChain.evaluate(&field, &Rules::TSU.pop_count(3)), 2);
+
+        // 見えない段が 2 段なら 1 段目のぷよはつながりに数えない
+        let mut field = FieldNaiveBit::new();
+        field.set(1, 0, Cell::RED);
+        field.set(2, 0, Cell::RED);
+        assert_eq!(Connectivity.evaluate(&field, &Rules::TSU), 4);
+        assert_eq!(Connectivity.evaluate(&field, &Rules::TSU.hidden_rows(2)), 1);
+    }
+
+    #[test]
+    fn finds_solution() {
+        let (field, info) = puzzle();
+        let beam = BeamSearch::new(8, PotentialChain);
+        let res = beam.search(&field, &info, &SolveOptions::default());
+        let solved = res.result.solution().unwrap();
+        assert_eq!(field::chain(&mut solved.clone()), 3);
+        assert_eq!(res.best.unwrap().1, 3);
+    }
+
+    #[test]
+    fn closure_evaluator_and_budget() {
+        let (field, info) = puzzle();
+        let beam = BeamSearch::new(1, |_: &FieldNaiveBit, _: &Rules| 0);
+        let res = beam.search(&field, &info, &SolveOptions::new().max_nodes(1));
+        assert_eq!(res.result, SolveResult::GaveUp(GiveUpReason::NodeLimit));
+        assert!(res.best.is_none());
+    }
+}
@@ -0,0 +1,198 @@
+//! 盤面の 1 マス
+//!
+//! 盤面の実装はそれぞれの詰め方 (u8 や 3 bit) で持つが、出し入れは必ず `Cell` で行う
+//! 数字との変換は `code` / `Cell::from_code` で、範囲外の数字はここで弾く
+
+use std::fmt;
+
+/// 色ぷよ
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Color {
+    Red,
+    Blue,
+    Green,
+    Yellow,
+    Purple,
+}
+
+impl Color {
+    pub const ALL: [Color; 5] = [Color::Red, Color::Blue, Color::Green, Color::Yellow, Color::Purple];
+
+    /// 2 赤, 3 青, 4 緑, 5 黄, 6 紫
+    pub fn code(self) -> u8 {
+        self as u8 + 2
+    }
+
+    pub fn from_code(code: u8) -> Option<Color> {
+        match code {
+            2 => Some(Color::Red),
+            3 => Some(Color::Blue),
+            4 => Some(Color::Green),
+            5 => Some(Color::Yellow),
+            6 => Some(Color::Purple),
+            _ => None,
+        }
+    }
+}
+
+/// 盤面の 1 マス
+/// 番号は 0 空白, 1 おじゃま, 2-6 色ぷよ, 7 固ぷよ (3 bit に収まる)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Cell {
+    #[default]
+    Empty,
+    Ojama,
+    Color(Color),
+    /// 固ぷよ。隣で消えるとおじゃまになる (2 回巻き込むと消える)
+    Hard,
+}
+
+/// 0-7 以外の番号
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidCell(pub u8);
+
+impl fmt::Display for InvalidCell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid cell code {}, expected 0-7", self.0)
+    }
+}
+
+impl std::error::Error for InvalidCell {}
+
+impl Cell {
+    pub const RED: Cell = Cell::Color(Color::Red);
+    pub const BLUE: Cell = Cell::Color(Color::Blue);
+    pub const GREEN: Cell = Cell::Color(Color::Green);
+    pub const YELLOW: Cell = Cell::Color(Color::Yellow);
+    pub const PURPLE: Cell = Cell::Color(Color::Purple);
+
+    pub fn code(self) -> u8 {
+        match self {
+            Cell::Empty => 0,
+            Cell::Ojama => 1,
+            Cell::Color(c) => c.code(),
+            Cell::Hard => 7,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Result<Cell, InvalidCell> {
+        match code {
+            0 => Ok(Cell::Empty),
+            1 => Ok(Cell::Ojama),
+            7 => Ok(Cell::Hard),
+            _ => Color::from_code(code).map(Cell::Color).ok_or(InvalidCell(code)),
+        }
+    }
+
+    /// 盤面の実装の中身から戻すとき用。中身は set でしか入らないので範囲外なら実装のバグ
+    #[inline]
+    pub(crate) fn decode(code: u8) -> Cell {
+        match Cell::from_code(code) {
+            Ok(cell) => cell,
+            Err(e) => unreachable!("{}", e),
+        }
+    }
+
+    /// 盤面の文字 -> マス
+    /// . 空白, @ おじゃま, r 赤, b 青, g 緑, y 黄, p 紫, h 固ぷよ
+    pub fn from_char(c: char) -> Option<Cell> {
+        match c {
+            '.' => Some(Cell::Empty),
+            '@' => Some(Cell::Ojama),
+            'r' => Some(Cell::RED),
+            'b' => Some(Cell::BLUE),
+            'g' => Some(Cell::GREEN),
+            'y' => Some(Cell::YELLOW),
+            'p' => Some(Cell::PURPLE),
+            'h' => Some(Cell::Hard),
+            _ => None,
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        match self {
+            Cell::Empty => '.',
+            Cell::Ojama => '@',
+            Cell::RED => 'r',
+            Cell::BLUE => 'b',
+            Cell::GREEN => 'g',
+            Cell::YELLOW => 'y',
+            Cell::PURPLE => 'p',
+            Cell::Hard => 'h',
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(self) -> bool {
+        self == Cell::Empty
+    }
+
+    /// 色ぷよならその色
+    #[inline]
+    pub fn color(self) -> Option<Color> {
+        match self {
+            Cell::Color(c) => Some(c),
+            _ => None,
+        }
+    }
+
+    /// 隣のぷよが消えたあとのこのマス
+    #[inline]
+    pub fn hit(self) -> Cell {
+        match self {
+            Cell::Ojama => Cell::Empty,
+            Cell::Hard => Cell::Ojama,
+            c => c,
+        }
+    }
+}
+
+impl From<Color> for Cell {
+    fn from(c: Color) -> Self {
+        Cell::Color(c)
+    }
+}
+
+impl From<Cell> for u8 {
+    fn from(c: Cell) -> Self {
+        c.code()
+    }
+}
+
+impl TryFrom<u8> for Cell {
+    type Error = InvalidCell;
+
+    fn try_from(code: u8) -> Result<Self, Self::Error> {
+        Cell::from_code(code)
+    }
+}
+
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn codes_round_trip() {
+        for code in 0..8 {
+            let cell = Cell::from_code(code).unwrap();
+            assert_eq!(cell.code(), code);
+            assert_eq!(Cell::from_char(cell.to_char()), Some(cell));
+        }
+        assert_eq!(Cell::from_code(8), Err(InvalidCell(8)));
+        assert_eq!(Cell::try_from(255), Err(InvalidCell(255)));
+        assert_eq!(Cell::from_char('x'), None);
+    }
+
+    #[test]
+    fn hit() {
+        assert_eq!(Cell::Hard.hit(), Cell::Ojama);
+        assert_eq!(Cell::Hard.hit().hit(), Cell::Empty);
+        assert_eq!(Cell::RED.hit(), Cell::RED);
+    }
+}
@@ -0,0 +1,203 @@
+//! 連鎖の途中の盤面を 1 コマずつ取り出す
+//!
+//! 置いた直後、そのあと連鎖ごとに 消えた直後 (落ちる前) と 落ちた後 の順に出てくる
+//! 盤面の表示はそれぞれの Field の Display を使う
+
+use std::fmt;
+
+use crate::field::{pop_groups, Field, PoppedGroup};
+use crate::rules::Rules;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameKind {
+    /// 置いた直後 (発火前)
+    Placed,
+    /// chain 連鎖目 (1 始まり) で消えた直後。消えたぷよは groups にある
+    Popped { chain: u32, groups: Vec<PoppedGroup> },
+    /// chain 連鎖目で消えたあと落ちた。distance は一番長く落ちたぷよの段数
+    Fell { chain: u32, distance: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame<F> {
+    pub kind: FrameKind,
+    pub field: F,
+}
+
+impl<F> Frame<F> {
+    /// このコマで消えたか
+    pub fn is_popped(&self, y: usize, x: usize) -> bool {
+        match &self.kind {
+            FrameKind::Popped { groups, .. } => groups.iter().any(|g| g.cells.contains(&(y, x))),
+            _ => false,
+        }
+    }
+}
+
+impl<F: fmt::Display> fmt::Display for Frame<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            FrameKind::Placed => writeln!(f, "placed")?,
+            FrameKind::Popped { chain, groups } => {
+                let popped: usize = groups.iter().map(|g| g.cells.len()).sum();
+                writeln!(f, "chain {}: popped {}", chain, popped)?
+            }
+            FrameKind::Fell { chain, .. } => writeln!(f, "chain {}: fell", chain)?,
+        }
+        write!(f, "{}", self.field)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Start,
+    Pop,
+    Fall,
+    Done,
+}
+
+/// chain_frames が返すイテレータ
+#[derive(Debug, Clone)]
+pub struct ChainFrames<F> {
+    field: F,
+    rules: Rules,
+    chain: u32,
+    step: Step,
+}
+
+/// field を rules で連鎖させたときのコマを順に返す
+/// field は変えない。最後のコマの盤面が field::chain_with のあとの盤面と同じになる
+pub fn chain_frames<F: Field + Clone>(field: &F, rules: &Rules) -> ChainFrames<F> {
+    ChainFrames {
+        field: field.clone(),
+        rules: *rules,
+        chain: 0,
+        step: Step::Start,
+    }
+}
+
+impl<F: Field + Clone> Iterator for ChainFrames<F> {
+    type Item = Frame<F>;
+
+    fn next(&mut self) -> Option<Frame<F>> {
+        let kind = match self.step {
+            Step::Start => {
+                self.step = Step::Pop;
+                FrameKind::Placed
+            }
+            Step::Pop => {
+                let (next_field, groups) = match pop_groups(&self.field, &self.rules) {
+                    Some(popped) => popped,
+                    None => {
+                        self.step = Step::Done;
+                        return None;
+                    }
+                };
+                self.chain += 1;
+                self.field = next_field;
+                self.step = Step::Fall;
+                FrameKind::Popped {
+                    chain: self.chain,
+                    groups,
+                }
+            }
+            Step::Fall => {
+                let distance = self.field.fall();
+                self.step = Step::Pop;
+                FrameKind::Fell {
+                    chain: self.chain,
+                    distance,
+                }
+            }
+            Step::Done => return None,
+        };
+        Some(Frame {
+            kind,
+            field: self.field.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cell::{Cell, Color};
+    use crate::field;
+    use crate::field1d::Field1D;
+    use crate::field_naive_bit::FieldNaiveBit;
+    use crate::naive_field::NaiveField;
+
+    /// 1 連鎖目で赤と黄が同時に消えて間のおじゃまも消え、青が 3 段と 1 段落ちて 2 連鎖目で消える
+    fn double_pop<F: Field>() -> F {
+        let mut field = F::new();
+        for (y, x) in [(12, 2), (12, 3), (11, 3), (10, 3)] {
+            field.set(y, x, Cell::RED);
+        }
+        for y in 9..13 {
+            field.set(y, 5, Cell::YELLOW);
+        }
+        field.set(12, 4, Cell::Ojama);
+        for (y, x) in [(9, 3), (8, 3), (11, 4), (10, 4)] {
+            field.set(y, x, Cell::BLUE);
+        }
+        field
+    }
+
+    fn frames<F: Field + Clone>() -> Vec<Frame<F>> {
+        chain_frames(&double_pop::<F>(), &Rules::TSU).collect()
+    }
+
+    #[test]
+    fn two_chain_frames() {
+        let frames = frames::<NaiveField>();
+        let kinds: Vec<_> = frames
+            .iter()
+            .map(|f| match &f.kind {
+                FrameKind::Placed => "placed",
+                FrameKind::Popped { .. } => "popped",
+                FrameKind::Fell { .. } => "fell",
+            })
+            .collect();
+        assert_eq!(kinds, ["placed", "popped", "fell", "popped", "fell"]);
+
+        assert_eq!(frames[0].field, double_pop());
+        match &frames[1].kind {
+            FrameKind::Popped { chain, groups } => {
+                assert_eq!(*chain, 1);
+                let colors: Vec<_> = groups.iter().map(|g| (g.color, g.cells.len())).collect();
+                assert_eq!(colors, [(Color::Yellow, 4), (Color::Red, 4)]);
+            }
+            kind => panic!("unexpected {:?}", kind),
+        }
+        // 消えた直後はまだ落ちていない。巻き込まれたおじゃまは消えたぷよには入らない
+        assert!(frames[1].is_popped(10, 3));
+        assert!(!frames[1].is_popped(12, 4));
+        assert_eq!(frames[1].field.get(12, 4), Cell::Empty);
+        assert_eq!(frames[1].field.get(9, 3), Cell::BLUE);
+        assert_eq!(frames[2].kind, FrameKind::Fell { chain: 1, distance: 3 });
+        assert_eq!(frames[2].field.get(12, 3), Cell::BLUE);
+        assert_eq!(frames[2].field.get(12, 4), Cell::BLUE);
+
+        assert!(frames[3].to_string().starts_with("chain 2: popped 4\n"));
+        assert_eq!(frames[4].kind, FrameKind::Fell { chain: 2, distance: 0 });
+        let mut fired = double_pop::<NaiveField>();
+        assert_eq!(field::chain(&mut fired), 2);
+        assert_eq!(frames.last().unwrap().field, fired);
+        assert!(fired.is_empty());
+    }
+
+    #[test]
+    fn any_field() {
+        let naive = frames::<NaiveField>();
+        let one_d = frames::<Field1D>();
+        let bit = frames::<FieldNaiveBit>();
+        assert_eq!(naive.len(), one_d.len());
+        assert_eq!(naive.len(), bit.len());
+        for ((a, b), c) in naive.iter().zip(one_d.iter()).zip(bit.iter()) {
+            assert_eq!(a.kind, b.kind);
+            assert_eq!(a.kind, c.kind);
+        }
+        // 消えなければ置いた直後だけ
+        assert_eq!(chain_frames(&NaiveField::new(), &Rules::TSU).count(), 1);
+    }
+}
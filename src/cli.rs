@@ -0,0 +1,731 @@
+//! コマンドライン
+//! main.rs からは run を呼ぶだけ
+
+use std::fmt::Display;
+use std::fs;
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::difficulty::analyze;
+use crate::failure::FailureReport;
+use crate::field::Field;
+use crate::field1d::Field1D;
+use crate::field_naive_bit::FieldNaiveBit;
+use crate::generator::Generator;
+use crate::hint::{hint as make_hint, Hint};
+use crate::naive_field::NaiveField;
+use crate::nazo_url::parse_url;
+use crate::nazopuyo_info::NazopuyoInfo;
+use crate::nazopuyo_solver::Solver;
+use crate::placement::{parse_placements, verify as verify_placements};
+use crate::progress::{Progress, ProgressObserver};
+use crate::pruning::ChainUpperBound;
+use crate::render::{placed_cells, Renderer, Style};
+use crate::rules::Rules;
+use crate::puzzle_text::{parse_puzzle, write_puzzle};
+use crate::solve_options::{SolveOptions, SolveResult};
+use crate::solver_builder::{BuildError, SolverBuilder};
+
+const USAGE: &str = "\
+usage: rust_nazopuyo_solver solve [options] <file>
+       rust_nazopuyo_solver solve [options] --batch <dir>
+       rust_nazopuyo_solver verify [--format text|json] <file> <placement>...
+       rust_nazopuyo_solver hint [--format text|json] <file> [<placement>...]
+       rust_nazopuyo_solver difficulty [--format text|json] [--timeout s] <file>
+       rust_nazopuyo_solver generate [--seed n] [--chain n] [--pieces n] [--colors n]
+       rust_nazopuyo_solver bench [--field naive|1d|bit]
+
+puzzle files are in the text format (see puzzles/*.txt) or a pn.html URL
+placements are a column 1-6 and the side of the child puyo (u r d l), e.g. 3u 4r
+
+options:
+  --format text|json                 output format (default: text)
+  --solver recursive|stack|parallel  search backend (default: recursive)
+  --field naive|1d|bit               field representation (default: bit)
+  --timeout <seconds>                give up after this many seconds
+  --max-nodes <n>                    give up after visiting this many nodes
+  --prune                            enable chain upper bound pruning
+  --rules tsu|classic|fever          rule set (default: tsu)
+  --style ansi|ascii|unicode         how to draw solved boards, with the next
+                                     queue and the placed puyos highlighted
+  --progress                         print progress to stderr
+  --explain                          when there is no solution, report the closest
+                                     boards and why branches were cut
+
+generate options:
+  --seed <n>                         random seed (default: from the clock)
+  --chain <n>                        chain length (default: 3)
+  --pieces <n>                       number of pairs, 1-10 (default: 2)
+  --colors <n>                       number of colors, 2-5 (default: 4)
+
+exit status: 0 if every puzzle was solved (or the answer is correct),
+1 if some were not, 2 on errors";
+
+/// 終了コード
+pub const EXIT_SOLVED: i32 = 0;
+pub const EXIT_UNSOLVED: i32 = 1;
+pub const EXIT_ERROR: i32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Backend {
+    Recursive,
+    Stack,
+    Parallel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldKind {
+    Naive,
+    OneD,
+    Bit,
+}
+
+#[derive(Debug)]
+struct Config {
+    format: Format,
+    backend: Backend,
+    field: FieldKind,
+    rules: Rules,
+    /// None なら盤面の Display で出す
+    style: Option<Style>,
+    options: SolveOptions,
+    prune: bool,
+    explain: bool,
+    batch: bool,
+    path: Option<PathBuf>,
+    /// path より後ろの引数 (verify の置き方)
+    rest: Vec<String>,
+    seed: Option<u64>,
+    chain: u32,
+    pieces: usize,
+    colors: u8,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            format: Format::Text,
+            backend: Backend::Recursive,
+            field: FieldKind::Bit,
+            rules: Rules::default(),
+            style: None,
+            options: SolveOptions::default(),
+            prune: false,
+            explain: false,
+            batch: false,
+            path: None,
+            rest: vec![],
+            seed: None,
+            chain: 3,
+            pieces: 2,
+            colors: 4,
+        }
+    }
+}
+
+/// 途中経過を標準エラーに出す
+struct StderrProgress;
+
+impl ProgressObserver for StderrProgress {
+    fn on_progress(&self, p: &Progress) {
+        eprintln!(
+            "{} nodes, {:.1}% of first moves, table hit rate {:.1}%, {:?}",
+            p.nodes,
+            p.completion() * 100.0,
+            p.hit_rate() * 100.0,
+            p.elapsed
+        );
+    }
+}
+
+#[cfg(feature = "serde")]
+pub(crate) trait CliField:
+    Field + Clone + Eq + Hash + Display + Send + 'static + serde::Serialize
+{
+}
+#[cfg(feature = "serde")]
+impl<T> CliField for T where T: Field + Clone + Eq + Hash + Display + Send + 'static + serde::Serialize {}
+
+#[cfg(not(feature = "serde"))]
+pub(crate) trait CliField: Field + Clone + Eq + Hash + Display + Send + 'static {}
+#[cfg(not(feature = "serde"))]
+impl<T> CliField for T where T: Field + Clone + Eq + Hash + Display + Send + 'static {}
+
+/// args は実行ファイル名を除いたもの。終了コードを返す
+pub fn run(args: &[String]) -> i32 {
+    match args.first().map(|s| s.as_str()) {
+        Some("solve") => match parse_args(&args[1..], false) {
+            Ok(config) => solve(&config),
+            Err(e) => usage_error(&e),
+        },
+        Some("verify") => match parse_args(&args[1..], true) {
+            Ok(config) => verify(&config),
+            Err(e) => usage_error(&e),
+        },
+        Some("hint") => match parse_args(&args[1..], true) {
+            Ok(config) => hint(&config),
+            Err(e) => usage_error(&e),
+        },
+        Some("difficulty") => match parse_args(&args[1..], false) {
+            Ok(config) => difficulty(&config),
+            Err(e) => usage_error(&e),
+        },
+        Some("generate") => match parse_args(&args[1..], false) {
+            Ok(config) => generate(&config),
+            Err(e) => usage_error(&e),
+        },
+        Some("bench") => match parse_args(&args[1..], false) {
+            Ok(config) => {
+                match config.field {
+                    FieldKind::Naive => crate::field::kenny_bench::<NaiveField>(),
+                    FieldKind::OneD => crate::field::kenny_bench::<Field1D>(),
+                    FieldKind::Bit => crate::field::kenny_bench::<FieldNaiveBit>(),
+                }
+                EXIT_SOLVED
+            }
+            Err(e) => usage_error(&e),
+        },
+        Some("-h") | Some("--help") | Some("help") => {
+            println!("{}", USAGE);
+            EXIT_SOLVED
+        }
+        Some(cmd) => usage_error(&format!("unknown command {:?}", cmd)),
+        None => usage_error("missing command"),
+    }
+}
+
+fn usage_error(message: &str) -> i32 {
+    eprintln!("error: {}\n\n{}", message, USAGE);
+    EXIT_ERROR
+}
+
+/// rest が true なら 2 つ目以降の引数を config.rest に入れる
+fn parse_args(args: &[String], rest: bool) -> Result<Config, String> {
+    let mut config = Config::default();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value", name))
+        };
+        match arg.as_str() {
+            "--format" => {
+                config.format = match value(arg)?.as_str() {
+                    "text" => Format::Text,
+                    "json" => Format::Json,
+                    v => return Err(format!("unknown format {:?}", v)),
+                }
+            }
+            "--solver" => {
+                config.backend = match value(arg)?.as_str() {
+                    "recursive" => Backend::Recursive,
+                    "stack" => Backend::Stack,
+                    "parallel" => Backend::Parallel,
+                    v => return Err(format!("unknown solver {:?}", v)),
+                }
+            }
+            "--field" => {
+                config.field = match value(arg)?.as_str() {
+                    "naive" => FieldKind::Naive,
+                    "1d" => FieldKind::OneD,
+                    "bit" => FieldKind::Bit,
+                    v => return Err(format!("unknown field {:?}", v)),
+                }
+            }
+            "--rules" => {
+                let v = value(arg)?;
+                config.rules = match Rules::from_name(&v) {
+                    Some(rules) => rules,
+                    // 太陽ぷよは Cell に無い (rules のモジュールの説明を参照)
+                    None if v == "sun" => return Err("sun puyo rules are not supported".to_string()),
+                    None => return Err(format!("unknown rules {:?}", v)),
+                };
+            }
+            "--style" => {
+                let v = value(arg)?;
+                config.style = Some(Style::from_name(&v).ok_or_else(|| format!("unknown style {:?}", v))?);
+            }
+            "--timeout" => {
+                let v = value(arg)?;
+                // 負の数, NaN, 大きすぎる数は Duration にできない
+                let timeout = v.parse().ok().and_then(|secs| Duration::try_from_secs_f64(secs).ok());
+                config.options.timeout =
+                    Some(timeout.ok_or_else(|| format!("bad timeout {:?}, expected a number of seconds", v))?);
+            }
+            "--max-nodes" => {
+                let v = value(arg)?;
+                config.options.max_nodes = Some(v.parse().map_err(|_| format!("bad node count {:?}", v))?);
+            }
+            "--seed" => {
+                let v = value(arg)?;
+                config.seed = Some(v.parse().map_err(|_| format!("bad seed {:?}", v))?);
+            }
+            "--chain" => {
+                let v = value(arg)?;
+                config.chain = match v.parse() {
+                    Ok(n) if n >= 1 => n,
+                    _ => return Err(format!("bad chain count {:?}", v)),
+                };
+            }
+            "--pieces" => {
+                let v = value(arg)?;
+                config.pieces = match v.parse() {
+                    Ok(n) if (1..=10).contains(&n) => n,
+                    _ => return Err(format!("bad piece count {:?}, expected 1-10", v)),
+                };
+            }
+            "--colors" => {
+                let v = value(arg)?;
+                config.colors = match v.parse() {
+                    Ok(n) if (2..=5).contains(&n) => n,
+                    _ => return Err(format!("bad color count {:?}, expected 2-5", v)),
+                };
+            }
+            "--prune" => config.prune = true,
+            "--explain" => config.explain = true,
+            "--progress" => config.options.progress = Some(Arc::new(StderrProgress)),
+            "--batch" => {
+                config.batch = true;
+                config.path = Some(PathBuf::from(value(arg)?));
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option {:?}", arg)),
+            _ => {
+                if config.path.is_none() {
+                    config.path = Some(PathBuf::from(arg));
+                } else if rest {
+                    config.rest.push(arg.clone());
+                } else {
+                    return Err(format!("unexpected argument {:?}", arg));
+                }
+            }
+        }
+    }
+    if cfg!(not(feature = "serde")) && config.format == Format::Json {
+        return Err("json output needs the \"serde\" feature".to_string());
+    }
+    Ok(config)
+}
+
+/// テキスト形式か pn.html の URL を読んで、rules で解く問題にする
+pub(crate) fn read_puzzle<F: Field>(path: &Path, rules: Rules) -> Result<(F, NazopuyoInfo), String> {
+    read_puzzle_file(path).map(|(field, info)| (field, info.rules(rules)))
+}
+
+fn read_puzzle_file<F: Field>(path: &Path) -> Result<(F, NazopuyoInfo), String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let trimmed = text.trim();
+    if !trimmed.contains('\n') && trimmed.contains("__") {
+        return parse_url(trimmed).map_err(|e| format!("{}: {}", path.display(), e));
+    }
+    parse_puzzle(&text).map_err(|e| format!("{}:{}", path.display(), e))
+}
+
+/// ディレクトリ内の .txt を名前順に
+fn puzzle_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let mut files = vec![];
+    for entry in entries {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.extension().is_some_and(|e| e == "txt") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn solve(config: &Config) -> i32 {
+    match config.field {
+        FieldKind::Naive => solve_as::<NaiveField>(config),
+        FieldKind::OneD => solve_as::<Field1D>(config),
+        FieldKind::Bit => solve_as::<FieldNaiveBit>(config),
+    }
+}
+
+struct Report<F> {
+    path: PathBuf,
+    /// 読めなかった、解き始められなかったなら Err
+    result: Result<SolveResult<F>, String>,
+    elapsed: Duration,
+    /// --explain で解が無かったとき
+    failure: Option<FailureReport<F>>,
+}
+
+fn solve_as<F: CliField>(config: &Config) -> i32 {
+    let path = match &config.path {
+        Some(path) => path,
+        None => return usage_error("missing puzzle file"),
+    };
+    let files = if config.batch {
+        match puzzle_files(path) {
+            Ok(files) => files,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                return EXIT_ERROR;
+            }
+        }
+    } else {
+        vec![path.clone()]
+    };
+
+    let reports = solve_files::<F>(files, config);
+    if config.format == Format::Json {
+        print_json(&reports, config.batch);
+    }
+
+    if reports.iter().any(|r| r.result.is_err()) {
+        EXIT_ERROR
+    } else if reports.iter().all(|r| r.result.as_ref().is_ok_and(|r| r.is_solved())) {
+        EXIT_SOLVED
+    } else {
+        EXIT_UNSOLVED
+    }
+}
+
+/// 1 つずつ解いて、テキスト形式ならその場で出す
+/// 読めないファイルがあっても残りは解く
+fn solve_files<F: CliField>(files: Vec<PathBuf>, config: &Config) -> Vec<Report<F>> {
+    let mut reports = vec![];
+    for file in files {
+        match solve_file::<F>(&file, config) {
+            Ok((report, field, info)) => {
+                if config.format == Format::Text {
+                    print_text(&report, config.style, &field, &info);
+                }
+                reports.push(report);
+            }
+            Err(e) => {
+                eprintln!("error: {}", e);
+                reports.push(Report {
+                    path: file,
+                    result: Err(e),
+                    elapsed: Duration::ZERO,
+                    failure: None,
+                });
+            }
+        }
+    }
+    reports
+}
+
+fn solve_file<F: CliField>(file: &Path, config: &Config) -> Result<(Report<F>, F, NazopuyoInfo), String> {
+    let (field, info) = read_puzzle::<F>(file, config.rules)?;
+    let start = Instant::now();
+    let result = solve_one(field.clone(), &info, config).map_err(|e| format!("{}: {}", file.display(), e))?;
+    let elapsed = start.elapsed();
+    let failure = if config.explain && result == SolveResult::NoSolution {
+        let mut solver = Solver::new(field.clone(), info.clone());
+        if config.prune {
+            solver.add_pruner(ChainUpperBound);
+        }
+        Some(solver.explain(&config.options))
+    } else {
+        None
+    };
+    let report = Report {
+        path: file.to_path_buf(),
+        result: Ok(result),
+        elapsed,
+        failure,
+    };
+    Ok((report, field, info))
+}
+
+fn solve_one<F: CliField>(field: F, info: &NazopuyoInfo, config: &Config) -> Result<SolveResult<F>, BuildError> {
+    let mut builder = SolverBuilder::new().field(field).info(info).options(config.options.clone());
+    if config.prune {
+        builder = builder.pruner(ChainUpperBound);
+    }
+    Ok(match config.backend {
+        Backend::Recursive => builder.build()?.solve_with(&config.options),
+        Backend::Parallel => builder.build()?.solve_multi_with(&config.options),
+        Backend::Stack => builder.build_stack()?.solve_with(&config.options),
+    })
+}
+
+fn hint(config: &Config) -> i32 {
+    let path = match &config.path {
+        Some(path) => path,
+        None => return usage_error("missing puzzle file"),
+    };
+    let played = match parse_placements(&config.rest.join(" ")) {
+        Ok(placements) => placements,
+        Err(e) => return usage_error(&e.to_string()),
+    };
+    let (field, info) = match read_puzzle::<FieldNaiveBit>(path, config.rules) {
+        Ok(puzzle) => puzzle,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return EXIT_ERROR;
+        }
+    };
+    let hint = match make_hint(&field, &info, &played, &config.options) {
+        Ok(hint) => hint,
+        Err(reason) => {
+            eprintln!("{}: gave up, {:?}", path.display(), reason);
+            return EXIT_UNSOLVED;
+        }
+    };
+    match config.format {
+        Format::Text => println!("{}: {}", path.display(), hint),
+        Format::Json => print_json_value(&hint),
+    }
+    match hint {
+        Hint::Next { .. } => EXIT_SOLVED,
+        Hint::Finished { verdict } if verdict.is_cleared() => EXIT_SOLVED,
+        _ => EXIT_UNSOLVED,
+    }
+}
+
+fn difficulty(config: &Config) -> i32 {
+    let path = match &config.path {
+        Some(path) => path,
+        None => return usage_error("missing puzzle file"),
+    };
+    let (field, info) = match read_puzzle::<FieldNaiveBit>(path, config.rules) {
+        Ok(puzzle) => puzzle,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return EXIT_ERROR;
+        }
+    };
+    let report = match analyze(&field, &info, &config.options) {
+        Ok(report) => report,
+        Err(reason) => {
+            eprintln!("{}: gave up, {:?}", path.display(), reason);
+            return EXIT_UNSOLVED;
+        }
+    };
+    match config.format {
+        Format::Text => println!("{}\n{}", path.display(), report),
+        Format::Json => print_json_value(&report),
+    }
+    if report.solutions > 0 {
+        EXIT_SOLVED
+    } else {
+        EXIT_UNSOLVED
+    }
+}
+
+fn generate(config: &Config) -> i32 {
+    let seed = config.seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0)
+    });
+    let mut generator = Generator::new(config.chain, config.pieces, config.colors, seed);
+    match generator.generate::<FieldNaiveBit>() {
+        Some((field, info)) => {
+            println!("# seed {}: {} 手で {} 連鎖", seed, info.next.len(), info.chain);
+            print!("{}", write_puzzle(&field, &info));
+            EXIT_SOLVED
+        }
+        None => {
+            eprintln!("error: no puzzle found with seed {}", seed);
+            EXIT_UNSOLVED
+        }
+    }
+}
+
+fn verify(config: &Config) -> i32 {
+    match config.field {
+        FieldKind::Naive => verify_as::<NaiveField>(config),
+        FieldKind::OneD => verify_as::<Field1D>(config),
+        FieldKind::Bit => verify_as::<FieldNaiveBit>(config),
+    }
+}
+
+fn verify_as<F: CliField>(config: &Config) -> i32 {
+    let path = match &config.path {
+        Some(path) => path,
+        None => return usage_error("missing puzzle file"),
+    };
+    let placements = match parse_placements(&config.rest.join(" ")) {
+        Ok(placements) => placements,
+        Err(e) => return usage_error(&e.to_string()),
+    };
+    let (field, info) = match read_puzzle::<F>(path, config.rules) {
+        Ok(puzzle) => puzzle,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return EXIT_ERROR;
+        }
+    };
+    let verdict = verify_placements(&field, &info, &placements);
+    match config.format {
+        Format::Text => println!("{}: {}", path.display(), verdict),
+        Format::Json => print_json_value(&verdict),
+    }
+    if verdict.is_cleared() {
+        EXIT_SOLVED
+    } else {
+        EXIT_UNSOLVED
+    }
+}
+
+#[cfg(feature = "serde")]
+fn print_json_value<T: serde::Serialize>(value: &T) {
+    println!("{}", serde_json::to_string_pretty(value).expect("failed to write json"));
+}
+
+#[cfg(not(feature = "serde"))]
+fn print_json_value<T>(_value: &T) {
+    unreachable!("rejected in parse_args");
+}
+
+/// style があれば puzzle から置いたぷよを目立たせて、ネクストと並べて描く
+fn print_text<F: Field + Display>(report: &Report<F>, style: Option<Style>, puzzle: &F, info: &NazopuyoInfo) {
+    let path = report.path.display();
+    let result = match &report.result {
+        Ok(result) => result,
+        Err(_) => return,
+    };
+    match result {
+        SolveResult::Solved(field) => {
+            println!("{}: solved in {:?}", path, report.elapsed);
+            match style {
+                Some(style) => {
+                    let renderer = Renderer::new(style).next(&info.next, 0).highlight(placed_cells(puzzle, field));
+                    println!("{}", renderer.render(field));
+                }
+                None => println!("{}", field),
+            }
+        }
+        SolveResult::NoSolution => println!("{}: no solution ({:?})", path, report.elapsed),
+        SolveResult::GaveUp(reason) => println!("{}: gave up, {:?} ({:?})", path, reason, report.elapsed),
+    }
+    if let Some(failure) = &report.failure {
+        println!("{}", failure);
+    }
+}
+
+#[cfg(feature = "serde")]
+fn print_json<F: serde::Serialize + Clone>(reports: &[Report<F>], batch: bool) {
+    #[derive(serde::Serialize)]
+    struct JsonReport<'a, F: Clone> {
+        file: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        result: Option<&'a SolveResult<F>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<&'a str>,
+        elapsed_ms: u128,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        failure: Option<&'a FailureReport<F>>,
+    }
+    let json: Vec<_> = reports
+        .iter()
+        .map(|r| JsonReport {
+            file: r.path.display().to_string(),
+            result: r.result.as_ref().ok(),
+            error: r.result.as_ref().err().map(|e| e.as_str()),
+            elapsed_ms: r.elapsed.as_millis(),
+            failure: r.failure.as_ref(),
+        })
+        .collect();
+    let out = if batch {
+        serde_json::to_string_pretty(&json)
+    } else {
+        serde_json::to_string_pretty(&json[0])
+    };
+    println!("{}", out.expect("failed to write json"));
+}
+
+#[cfg(not(feature = "serde"))]
+fn print_json<F>(_reports: &[Report<F>], _batch: bool) {
+    unreachable!("rejected in parse_args");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_options() {
+        let config = parse_args(&args("--solver stack --field 1d --max-nodes 10 --prune a.txt"), false).unwrap();
+        assert_eq!(config.backend, Backend::Stack);
+        assert_eq!(config.field, FieldKind::OneD);
+        assert_eq!(config.options.max_nodes, Some(10));
+        assert!(config.prune);
+        assert_eq!(config.path, Some(PathBuf::from("a.txt")));
+
+        assert!(parse_args(&args("--solver foo a.txt"), false).is_err());
+        assert!(parse_args(&args("--timeout"), false).is_err());
+        assert_eq!(
+            parse_args(&args("--timeout 1.5 a.txt"), false).unwrap().options.timeout,
+            Some(Duration::from_millis(1500))
+        );
+        for bad in ["-1", "NaN", "inf", "1e300", "soon"] {
+            assert!(parse_args(&args(&format!("--timeout {} a.txt", bad)), false).is_err(), "{}", bad);
+        }
+        assert!(parse_args(&args("a.txt b.txt"), false).is_err());
+
+        let config = parse_args(&args("--seed 5 --chain 4 --pieces 3 --colors 3"), false).unwrap();
+        assert_eq!((config.seed, config.chain, config.pieces, config.colors), (Some(5), 4, 3, 3));
+        assert!(parse_args(&args("--pieces 11"), false).is_err());
+        assert!(parse_args(&args("--colors 1"), false).is_err());
+
+        assert_eq!(parse_args(&args("--rules classic a.txt"), false).unwrap().rules, Rules::CLASSIC);
+        assert_eq!(
+            parse_args(&args("--rules sun a.txt"), false).unwrap_err(),
+            "sun puyo rules are not supported"
+        );
+        assert!(parse_args(&args("--rules sunny a.txt"), false).is_err());
+        assert_eq!(parse_args(&args("--style unicode a.txt"), false).unwrap().style, Some(Style::Unicode));
+        assert!(parse_args(&args("--style color a.txt"), false).is_err());
+    }
+
+    #[test]
+    fn exit_codes() {
+        let dir = std::env::temp_dir().join(format!("nazopuyo_cli_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let rows = "......\n".repeat(12);
+        let solvable = dir.join("a.txt");
+        fs::write(&solvable, format!("{}rrr...\nnext: rb\nchain: 1\n", rows.replacen("......\n", "", 1))).unwrap();
+        let unsolvable = dir.join("b.txt");
+        fs::write(&unsolvable, format!("{}next: rb\nchain: 1\n", rows)).unwrap();
+
+        let run_with = |a: String| run(&args(&a));
+        assert_eq!(run_with(format!("solve {}", solvable.display())), EXIT_SOLVED);
+        assert_eq!(run_with(format!("solve --solver stack {}", unsolvable.display())), EXIT_UNSOLVED);
+        assert_eq!(run_with(format!("solve --batch {}", dir.display())), EXIT_UNSOLVED);
+        assert_eq!(run_with(format!("solve --explain {}", unsolvable.display())), EXIT_UNSOLVED);
+        assert_eq!(run_with(format!("solve {}", dir.join("c.txt").display())), EXIT_ERROR);
+        assert_eq!(run_with("frobnicate".to_string()), EXIT_ERROR);
+        let chaining = dir.join("chaining.txt");
+        fs::write(&chaining, format!("{}rrrr..\nnext: rb\nchain: 1\n", rows.replacen("......\n", "", 1))).unwrap();
+        assert_eq!(run_with(format!("solve {}", chaining.display())), EXIT_ERROR);
+        // 解けないファイルがあっても、ほかのファイルの結果は残す
+        assert_eq!(run_with(format!("solve --batch {}", dir.display())), EXIT_ERROR);
+        let files = puzzle_files(&dir).unwrap();
+        let reports = solve_files::<FieldNaiveBit>(files, &Config::default());
+        assert_eq!(reports.len(), 3);
+        assert!(reports[0].result.as_ref().unwrap().is_solved());
+        assert_eq!(reports[1].result, Ok(SolveResult::NoSolution));
+        assert!(reports[2].result.as_ref().unwrap_err().contains("chaining.txt"));
+        fs::remove_file(&chaining).unwrap();
+
+        assert_eq!(run_with(format!("verify {} 4u", solvable.display())), EXIT_SOLVED);
+        assert_eq!(run_with(format!("verify {} 4d", solvable.display())), EXIT_UNSOLVED);
+        // 盤面の外は置けない手、読めない置き方はエラー
+        assert_eq!(run_with(format!("verify {} 9u", solvable.display())), EXIT_UNSOLVED);
+        assert_eq!(run_with(format!("verify {} 0u", solvable.display())), EXIT_ERROR);
+        assert_eq!(run_with(format!("hint {}", solvable.display())), EXIT_SOLVED);
+        assert_eq!(run_with(format!("hint {}", unsolvable.display())), EXIT_UNSOLVED);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
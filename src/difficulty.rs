@@ -0,0 +1,315 @@
+//! 問題の難しさの見積もり
+//!
+//! 置換表つきで全部の手を調べて、解の数や分岐の多さをまとめる
+//! 最後の手より前の連鎖は置けない手 (IllegalMove::EarlyChain) なので、途中の連鎖が要る解は無い
+//! 代わりに、そのせいで置けなかった手の数を early_chains に数える
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
+use std::hash::Hash;
+use std::ops::ControlFlow;
+
+use crate::field::{self, Field};
+use crate::nazopuyo_info::NazopuyoInfo;
+use crate::nazopuyo_solver::Solver;
+use crate::placement::for_each_move;
+use crate::solve_options::{Budget, GiveUpReason, SolveOptions, SolveResult};
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DifficultyReport {
+    /// 条件を満たす最終盤面の数 (発火前の盤面で数える)
+    pub solutions: usize,
+    /// Solver が最初の解を見つけるまでに訪れたノード数
+    pub solver_nodes: u64,
+    /// 全探索で調べた盤面の数
+    pub searched: u64,
+    /// 1 局面あたりの合法手 (違う盤面になるもの) の平均
+    pub branching: f64,
+    /// 1 手目から数えて各手で、解につながる手が何通りあるか (Solver の解に沿って見る)
+    pub choices: Vec<usize>,
+    /// 何手目 (0 始まり) から先は解につながる手が 1 通りしかないか
+    /// 最後の手まで複数あるなら None
+    pub forced_depth: Option<u32>,
+    /// どの解も 13 段目に置く必要がある
+    pub hidden_row_required: bool,
+    /// 最後の手より前に連鎖が起きるので置けなかった手の数 (調べた盤面ごと)
+    pub early_chains: u64,
+    /// 大きいほど難しい
+    pub score: f64,
+}
+
+impl DifficultyReport {
+    /// 難しさの点数
+    /// 読むノード数の桁 (log2) を土台に、初手から手順が決まっているほど、13 段目を使うほど足し、解が多いほど割る
+    fn compute_score(&mut self, pieces: usize) {
+        if self.solutions == 0 {
+            self.score = 0.0;
+            return;
+        }
+        let mut score = (1.0 + self.solver_nodes as f64).log2() + self.branching.max(1.0).log2();
+        if let Some(d) = self.forced_depth {
+            score += (pieces as u32 - d) as f64;
+        }
+        if self.hidden_row_required {
+            score += 3.0;
+        }
+        self.score = score / self.solutions as f64;
+    }
+}
+
+impl Display for DifficultyReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "score:            {:.2}", self.score)?;
+        writeln!(f, "solutions:        {}", self.solutions)?;
+        writeln!(f, "solver nodes:     {}", self.solver_nodes)?;
+        writeln!(f, "searched:         {}", self.searched)?;
+        writeln!(f, "branching:        {:.2}", self.branching)?;
+        writeln!(f, "choices per move: {:?}", self.choices)?;
+        match self.forced_depth {
+            Some(d) => writeln!(f, "forced from move: {}", d + 1)?,
+            None => writeln!(f, "forced from move: -")?,
+        }
+        writeln!(f, "hidden row:       {}", if self.hidden_row_required { "required" } else { "not required" })?;
+        write!(f, "early chains:     {} (not allowed before the last move)", self.early_chains)
+    }
+}
+
+struct Analyzer<'a, F> {
+    original: &'a F,
+    info: &'a NazopuyoInfo,
+    /// (盤面, 深さ) -> 解につながるか
+    /// 見えない段のぷよが消えるルールでは置いても盤面が変わらないことがあるので、深さも鍵に入れる
+    memo: HashMap<(F, u32), bool>,
+    expanded: u64,
+    children: u64,
+    solutions: usize,
+    hidden_row_solutions: usize,
+    early_chains: u64,
+}
+
+impl<F> Analyzer<'_, F>
+where
+    F: Field + Clone + Eq + Hash,
+{
+    fn uses_hidden_row(&self, field: &F) -> bool {
+        (0..F::WIDTH).any(|j| self.original.get(0, j).is_empty() && !field.get(0, j).is_empty())
+    }
+
+    /// 重複を除いた子と、最後の手より前に連鎖するので置けない手の数
+    fn children(&self, field: &F, depth: u32) -> (Vec<F>, u64) {
+        let rules = &self.info.rules;
+        let last = depth + 1 == self.info.next.len() as u32;
+        let mut children = vec![];
+        let mut seen = HashSet::new();
+        let mut early_chains = 0;
+        let _ = for_each_move(&mut field.clone(), self.info.next.pair(depth as usize), rules, |_, child| {
+            if !last && field::has_chain_with(child, rules) {
+                early_chains += 1;
+            } else if (last || field::is_alive_with(child, rules)) && seen.insert(child.clone()) {
+                children.push(child.clone());
+            }
+            ControlFlow::<()>::Continue(())
+        });
+        (children, early_chains)
+    }
+
+    fn search(&mut self, field: &F, depth: u32, budget: &Budget) -> Result<bool, GiveUpReason> {
+        if let Some(&res) = self.memo.get(&(field.clone(), depth)) {
+            return Ok(res);
+        }
+        if let Some(reason) = budget.visit(depth) {
+            return Err(reason);
+        }
+        let res = if depth == self.info.next.len() as u32 {
+            let solved = field::chain_with(&mut field.clone(), &self.info.rules) == self.info.chain;
+            if solved {
+                self.solutions += 1;
+                if self.uses_hidden_row(field) {
+                    self.hidden_row_solutions += 1;
+                }
+            }
+            solved
+        } else {
+            let (children, early_chains) = self.children(field, depth);
+            self.expanded += 1;
+            self.early_chains += early_chains;
+            self.children += children.len() as u64;
+            let mut any = false;
+            for child in children.iter() {
+                // 解の数を数えたいので見つかっても最後まで調べる
+                any |= self.search(child, depth + 1, budget)?;
+            }
+            any
+        };
+        self.memo.insert((field.clone(), depth), res);
+        Ok(res)
+    }
+
+    /// Solver と同じ順で最初の解へ降りながら、各手で解につながる手の数を数える
+    fn choices(&self) -> Vec<usize> {
+        let mut res = vec![];
+        let mut field = self.original.clone();
+        for depth in 0..self.info.next.len() as u32 {
+            let good: Vec<F> = self
+                .children(&field, depth)
+                .0
+                .into_iter()
+                .filter(|c| self.memo.get(&(c.clone(), depth + 1)) == Some(&true))
+                .collect();
+            match good.first() {
+                Some(next) => {
+                    res.push(good.len());
+                    field = next.clone();
+                }
+                None => break,
+            }
+        }
+        res
+    }
+}
+
+/// 問題の難しさを調べる。options の打ち切り条件に当たったらその理由を返す
+pub fn analyze<F>(field: &F, info: &NazopuyoInfo, options: &SolveOptions) -> Result<DifficultyReport, GiveUpReason>
+where
+    F: Field + Clone + Eq + Hash + Display + Send + 'static,
+{
+    // 打ち切り条件は Solver と全探索の 2 つで分け合う
+    let budget = Budget::new(options, info.next.len());
+    let mut solver = Solver::new(field.clone(), info.clone());
+    if let SolveResult::GaveUp(reason) = solver.solve_in(&budget) {
+        budget.finish();
+        return Err(reason);
+    }
+    let solver_nodes = budget.nodes();
+
+    let mut analyzer = Analyzer {
+        original: field,
+        info,
+        memo: HashMap::new(),
+        expanded: 0,
+        children: 0,
+        solutions: 0,
+        hidden_row_solutions: 0,
+        early_chains: 0,
+    };
+    let res = analyzer.search(field, 0, &budget);
+    budget.finish();
+    res?;
+
+    let choices = analyzer.choices();
+    let forced_depth = if analyzer.solutions == 0 || choices.last() != Some(&1) {
+        None
+    } else {
+        let free = choices.iter().rposition(|&c| c > 1);
+        Some(free.map_or(0, |i| i as u32 + 1))
+    };
+    let mut report = DifficultyReport {
+        solutions: analyzer.solutions,
+        solver_nodes,
+        searched: analyzer.memo.len() as u64,
+        branching: if analyzer.expanded == 0 {
+            0.0
+        } else {
+            analyzer.children as f64 / analyzer.expanded as f64
+        },
+        choices,
+        forced_depth,
+        hidden_row_required: analyzer.solutions > 0 && analyzer.hidden_row_solutions == analyzer.solutions,
+        early_chains: analyzer.early_chains,
+        score: 0.0,
+    };
+    report.compute_score(info.next.len());
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cell::Cell;
+    use crate::generator::Generator;
+    use crate::naive_field::NaiveField;
+    use crate::naive_next_puyo::NaiveNextPuyo;
+    use crate::rules::Rules;
+
+    #[test]
+    fn one_move() {
+        let mut field = NaiveField::new();
+        field.set(12, 0, Cell::RED);
+        field.set(12, 1, Cell::RED);
+        field.set(12, 2, Cell::RED);
+        let info = NazopuyoInfo::new(1, NaiveNextPuyo::from_vec(vec![[2, 3]]).unwrap());
+        let report = analyze(&field, &info, &SolveOptions::default()).unwrap();
+        assert_eq!(report.solutions, 11);
+        assert_eq!(report.choices, vec![11]);
+        assert_eq!(report.forced_depth, None);
+        assert!(!report.hidden_row_required);
+        assert_eq!(report.branching, 22.0);
+        assert_eq!(report.early_chains, 0);
+
+        // Solver と全探索で 1 つの打ち切り条件を分け合う
+        let options = SolveOptions::new().max_nodes(report.searched);
+        assert_eq!(analyze(&field, &info, &options), Err(GiveUpReason::NodeLimit));
+        let options = SolveOptions::new().max_nodes(report.searched + report.solver_nodes);
+        assert_eq!(analyze(&field, &info, &options), Ok(report));
+    }
+
+    #[test]
+    fn early_chains() {
+        // 1 手目で赤を足すと連鎖してしまうので、赤は 2 手目まで取っておく
+        let mut field = NaiveField::new();
+        field.set(12, 0, Cell::RED);
+        field.set(12, 1, Cell::RED);
+        field.set(12, 2, Cell::RED);
+        let info = NazopuyoInfo::new(1, NaiveNextPuyo::from_vec(vec![[2, 3], [2, 3]]).unwrap());
+        let report = analyze(&field, &info, &SolveOptions::default()).unwrap();
+        assert!(report.solutions > 0);
+        assert!(report.early_chains > 0);
+    }
+
+    #[test]
+    fn harder_puzzle_scores_higher() {
+        let easy = {
+            let mut field = NaiveField::new();
+            field.set(12, 0, Cell::RED);
+            field.set(12, 1, Cell::RED);
+            field.set(12, 2, Cell::RED);
+            let next = NaiveNextPuyo::from_vec(vec![[2, 3]]).unwrap();
+            analyze(&field, &NazopuyoInfo::new(1, next), &SolveOptions::default()).unwrap()
+        };
+        let (field, info) = Generator::new(3, 2, 4, 7).generate::<NaiveField>().unwrap();
+        let hard = analyze(&field, &info, &SolveOptions::default()).unwrap();
+        assert_eq!(hard.solutions, 1);
+        assert_eq!(hard.choices.last(), Some(&1));
+        assert_eq!(hard.choices.len(), info.next.len());
+        assert!(hard.score > easy.score, "{} <= {}", hard.score, easy.score);
+    }
+
+    #[test]
+    fn vanishing_hidden_row() {
+        // 1, 2 列目が 12 段目まで埋まっていると、そこへ横に置いた組は見えない段で消えて盤面が変わらない
+        // 同じ盤面でも深さが違えば答えが違うので、1 手目にそう置くのは解につながらない
+        let mut field = NaiveField::new();
+        for y in 1..13 {
+            field.set(y, 0, Cell::Ojama);
+            field.set(y, 1, Cell::Ojama);
+        }
+        let next = NaiveNextPuyo::from_vec(vec![[2, 2], [2, 2]]).unwrap();
+        let keep = analyze(&field, &NazopuyoInfo::new(1, next.clone()), &SolveOptions::default()).unwrap();
+        let info = NazopuyoInfo::new(1, next).rules(Rules::CLASSIC);
+        let vanish = analyze(&field, &info, &SolveOptions::default()).unwrap();
+        assert_eq!(keep.choices, vec![7, 4]);
+        assert_eq!(vanish.choices, keep.choices);
+        assert_eq!(vanish.solutions, keep.solutions);
+    }
+
+    #[test]
+    fn no_solution() {
+        let info = NazopuyoInfo::new(1, NaiveNextPuyo::from_vec(vec![[2, 3]]).unwrap());
+        let report = analyze(&NaiveField::new(), &info, &SolveOptions::default()).unwrap();
+        assert_eq!(report.solutions, 0);
+        assert_eq!(report.forced_depth, None);
+        assert_eq!(report.score, 0.0);
+        assert!(report.choices.is_empty());
+    }
+}
@@ -0,0 +1,206 @@
+//! 解が無かったときの説明
+//!
+//! ソルバーと同じ順で全部の手を調べ、どこで枝が切られたかと、一番惜しかった盤面を集める
+
+use std::collections::HashSet;
+use std::fmt::{self, Display};
+use std::hash::Hash;
+use std::ops::ControlFlow;
+
+use crate::field::{self, Field};
+use crate::nazopuyo_info::NazopuyoInfo;
+use crate::placement::for_each_move;
+use crate::pruning::{any_prune, Pruner};
+use crate::solve_options::{Budget, GiveUpReason, SolveOptions};
+
+/// 惜しかった盤面をいくつ覚えておくか
+const CLOSEST: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FailureReport<F> {
+    /// 全部置き終えた盤面で一番多かった連鎖数
+    pub best_chain: u32,
+    /// 連鎖数が目標に近い順の最終盤面 (発火前) と、その連鎖数
+    pub closest: Vec<(F, u32)>,
+    /// 全部置き終えた盤面の数
+    pub leaves: u64,
+    /// 窒息 (is_alive が false) で切った枝
+    pub dead: u64,
+    /// 最後の手より前に連鎖したので切った枝
+    pub early_chain: u64,
+    /// 置換表に既にあったので切った枝
+    pub duplicates: u64,
+    /// 枝刈り (Pruner) で切った局面
+    pub pruned: u64,
+    /// 打ち切り条件に当たったなら、その理由 (そこまでの集計が入っている)
+    pub gave_up: Option<GiveUpReason>,
+}
+
+impl<F> Default for FailureReport<F> {
+    fn default() -> Self {
+        Self {
+            best_chain: 0,
+            closest: vec![],
+            leaves: 0,
+            dead: 0,
+            early_chain: 0,
+            duplicates: 0,
+            pruned: 0,
+            gave_up: None,
+        }
+    }
+}
+
+impl<F: Display> Display for FailureReport<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(reason) = self.gave_up {
+            writeln!(f, "gave up ({:?}), counts are partial", reason)?;
+        }
+        writeln!(f, "best chain:         {}", self.best_chain)?;
+        writeln!(f, "final boards:       {}", self.leaves)?;
+        writeln!(f, "cut by death:       {}", self.dead)?;
+        writeln!(f, "cut by early chain: {}", self.early_chain)?;
+        writeln!(f, "duplicates:         {}", self.duplicates)?;
+        write!(f, "pruned:             {}", self.pruned)?;
+        for (board, chain) in self.closest.iter() {
+            write!(f, "\n{} chain:\n{}", chain, board)?;
+        }
+        Ok(())
+    }
+}
+
+struct Explainer<'a, F: Field> {
+    info: &'a NazopuyoInfo,
+    pruners: &'a [Box<dyn Pruner<F>>],
+    hash: HashSet<F>,
+    report: FailureReport<F>,
+}
+
+impl<F> Explainer<'_, F>
+where
+    F: Field + Clone + Eq + Hash,
+{
+    fn leaf(&mut self, field: &F) {
+        let chain = field::chain_with(&mut field.clone(), &self.info.rules);
+        self.report.leaves += 1;
+        self.report.best_chain = self.report.best_chain.max(chain);
+
+        let goal = self.info.chain;
+        let closest = &mut self.report.closest;
+        closest.push((field.clone(), chain));
+        closest.sort_by_key(|(_, c)| c.abs_diff(goal));
+        closest.truncate(CLOSEST);
+    }
+
+    fn dfs(&mut self, mut field: F, depth: u32, budget: &Budget) -> Result<(), GiveUpReason> {
+        if let Some(reason) = budget.visit(depth) {
+            return Err(reason);
+        }
+        if depth == self.info.next.len() as u32 {
+            self.leaf(&field);
+            return Ok(());
+        }
+        if any_prune(self.pruners, &field, self.info, depth) {
+            self.report.pruned += 1;
+            return Ok(());
+        }
+
+        let pair = self.info.next.pair(depth as usize);
+        let rules = self.info.rules;
+        let last = depth + 1 == self.info.next.len() as u32;
+        let flow = for_each_move(&mut field, pair, &rules, |_, child| {
+            let hit = self.hash.contains(child);
+            budget.lookup(hit);
+            if hit {
+                self.report.duplicates += 1;
+            } else if !last && !field::is_alive_with(child, &rules) {
+                self.report.dead += 1;
+            } else if !last && field::has_chain_with(child, &rules) {
+                self.report.early_chain += 1;
+            } else {
+                self.hash.insert(child.clone());
+                if let Err(reason) = self.dfs(child.clone(), depth + 1, budget) {
+                    return ControlFlow::Break(reason);
+                }
+            }
+            ControlFlow::Continue(())
+        });
+        match flow {
+            ControlFlow::Break(reason) => Err(reason),
+            ControlFlow::Continue(()) => Ok(()),
+        }
+    }
+}
+
+/// depth 手置いたあとの field から全部調べて、枝が切られた理由と惜しかった盤面をまとめる
+/// 解がある問題でも使える (そのときは closest の先頭が解になる)
+pub fn explain<F>(
+    field: &F,
+    info: &NazopuyoInfo,
+    depth: u32,
+    options: &SolveOptions,
+    pruners: &[Box<dyn Pruner<F>>],
+) -> FailureReport<F>
+where
+    F: Field + Clone + Eq + Hash,
+{
+    let mut explainer = Explainer {
+        info,
+        pruners,
+        hash: HashSet::new(),
+        report: FailureReport::default(),
+    };
+    let budget = Budget::new(options, info.next.len());
+    if let Err(reason) = explainer.dfs(field.clone(), depth, &budget) {
+        explainer.report.gave_up = Some(reason);
+    }
+    budget.finish();
+    explainer.report
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::naive_field::NaiveField;
+    use crate::puzzle_text::parse_puzzle;
+
+    fn puzzle(rows: &str, next: &str, chain: u32) -> (NaiveField, NazopuyoInfo) {
+        let text = format!("{}{}\nnext: {}\nchain: {}\n", "......\n".repeat(12), rows, next, chain);
+        parse_puzzle(&text).unwrap()
+    }
+
+    #[test]
+    fn mistyped_chain() {
+        // 1 連鎖しかできないのに 2 連鎖と書いてしまった
+        let (field, info) = puzzle("rrr...", "rb", 2);
+        let report = explain(&field, &info, 0, &SolveOptions::default(), &[]);
+        assert_eq!(report.best_chain, 1);
+        assert_eq!(report.leaves, 22);
+        assert_eq!(report.closest.len(), CLOSEST);
+        assert!(report.closest.iter().all(|(_, c)| *c == 1));
+        assert_eq!(report.gave_up, None);
+    }
+
+    #[test]
+    fn counts_cut_branches() {
+        let (field, info) = puzzle("rrr...", "rr bb", 1);
+        let report = explain(&field, &info, 0, &SolveOptions::default(), &[]);
+        assert!(report.early_chain > 0);
+        assert_eq!(report.dead, 0);
+        // 同じ色の組は縦に置くと上下を入れ替えても同じ盤面
+        assert!(report.duplicates > 0);
+
+        let report = explain(&field, &info, 0, &SolveOptions::new().max_nodes(5), &[]);
+        assert_eq!(report.gave_up, Some(GiveUpReason::NodeLimit));
+    }
+
+    #[test]
+    fn counts_death() {
+        let rows = "..y...\n..g...\n..y...\n..g...\n..y...\n..g...\n..y...\n..g...\n..y...\n..g...";
+        let text = format!("{}{}\nnext: rb rb\nchain: 1\n", "......\n".repeat(3), rows);
+        let (field, info) = parse_puzzle::<NaiveField>(&text).unwrap();
+        let report = explain(&field, &info, 0, &SolveOptions::default(), &[]);
+        assert!(report.dead > 0);
+    }
+}
@@ -0,0 +1,181 @@
+//! フィーバーモード
+//!
+//! フィーバーモードでは組まれた連鎖のタネが降ってくる。
+//! タネの盤面を読み込んで、手持ちのツモをどう置いて発火すると一番長く連鎖するかを探す
+//!
+//! ```text
+//! # 3 連鎖
+//! ..b...
+//! .rbg..
+//! .rrbgg
+//!
+//! # 4 連鎖
+//! ...
+//! ```
+//!
+//! - 盤面は puzzle_text と同じ書き方で、空行で区切って何個でも並べる
+//! - 行は下に詰める (HEIGHT 行以下なら何行でもよい)
+//! - 盤面の直前の `#` の行がタネの名前
+
+use std::collections::HashSet;
+use std::hash::Hash;
+
+use crate::cell::Cell;
+use crate::field::{self, Field};
+use crate::naive_next_puyo::NaiveNextPuyo;
+use crate::nazopuyo_info::NazopuyoInfo;
+use crate::parallel_solver::expand;
+use crate::puzzle_text::{error, parse_row, rows_to_field, ParseError, ParseErrorKind};
+use crate::rules::Rules;
+use crate::solve_options::{Budget, GiveUpReason, SolveOptions};
+
+/// 連鎖のタネ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeverSeed<F> {
+    pub name: Option<String>,
+    pub field: F,
+}
+
+/// 空行で区切ったタネを全部読む
+pub fn parse_seeds<F: Field>(text: &str) -> Result<Vec<FeverSeed<F>>, ParseError> {
+    let mut seeds = vec![];
+    let mut name: Option<String> = None;
+    let mut rows: Vec<Vec<Cell>> = vec![];
+
+    let mut flush = |name: &mut Option<String>, rows: &mut Vec<Vec<Cell>>| {
+        if !rows.is_empty() {
+            seeds.push(FeverSeed {
+                name: name.take(),
+                field: rows_to_field(rows),
+            });
+            rows.clear();
+        }
+        // 盤面の直前にない名前は使わない
+        *name = None;
+    };
+
+    for (n, line) in text.lines().enumerate() {
+        let line_no = n + 1;
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            flush(&mut name, &mut rows);
+            continue;
+        }
+        if let Some(comment) = trimmed.strip_prefix('#') {
+            if rows.is_empty() && name.is_none() {
+                name = Some(comment.trim().to_string());
+            }
+            continue;
+        }
+        if let Some(colon) = trimmed.find(':') {
+            let key = trimmed[..colon].trim().to_string();
+            return Err(error(line_no, 1, ParseErrorKind::UnknownKey(key)));
+        }
+        if rows.len() == F::HEIGHT {
+            return Err(error(line_no, 1, ParseErrorKind::TooManyRows { max: F::HEIGHT }));
+        }
+        rows.push(parse_row::<F>(trimmed, line_no)?);
+    }
+    flush(&mut name, &mut rows);
+
+    Ok(seeds)
+}
+
+/// ツモを全部置いてから発火して、一番長い連鎖になる盤面 (発火前) とその連鎖数
+/// 最後の手より前に消える手はソルバーと同じく読まない。どう置いても消えなければ None
+pub fn best_trigger<F>(
+    field: &F,
+    next: &NaiveNextPuyo,
+    rules: &Rules,
+    options: &SolveOptions,
+) -> Result<Option<(F, u32)>, GiveUpReason>
+where
+    F: Field + Clone + Eq + Hash,
+{
+    let info = NazopuyoInfo::new(0, next.clone()).rules(*rules);
+    let budget = Budget::new(options, next.len());
+    let mut layer = vec![field.clone()];
+    let mut children = vec![];
+
+    for depth in 0..next.len() as u32 {
+        let mut seen = HashSet::new();
+        let mut next_layer = vec![];
+        for f in layer.iter() {
+            if let Some(reason) = budget.visit(depth) {
+                budget.finish();
+                return Err(reason);
+            }
+            expand(&mut f.clone(), &info, depth, &mut children);
+            for child in children.drain(..) {
+                let hit = !seen.insert(child.clone());
+                budget.lookup(hit);
+                if !hit {
+                    next_layer.push(child);
+                }
+            }
+        }
+        layer = next_layer;
+    }
+    budget.finish();
+
+    // 同じ連鎖数なら先に見つけた (真ん中寄りの列に置いた) ほう
+    let mut best: Option<(F, u32)> = None;
+    for f in layer {
+        let chain = field::chain_with(&mut f.clone(), rules);
+        if chain > 0 && best.as_ref().is_none_or(|(_, c)| *c < chain) {
+            best = Some((f, chain));
+        }
+    }
+    Ok(best)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::naive_field::NaiveField;
+
+    const SEEDS: &str = "\
+# 2 連鎖
+.b....
+gb....
+br....
+br....
+
+# 名前なし
+
+.gg...
+.rr...
+";
+
+    #[test]
+    fn parse() {
+        let seeds = parse_seeds::<NaiveField>(SEEDS).unwrap();
+        assert_eq!(seeds.len(), 2);
+        assert_eq!(seeds[0].name.as_deref(), Some("2 連鎖"));
+        assert_eq!(seeds[0].field.get(12, 1), Cell::RED);
+        assert_eq!(seeds[0].field.get(9, 1), Cell::BLUE);
+        assert_eq!(seeds[1].name, None);
+        assert_eq!(seeds[1].field.get(11, 1), Cell::GREEN);
+
+        let err = parse_seeds::<NaiveField>("rrr...\nnext: rr\n").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnknownKey("next".to_string()));
+        let err = parse_seeds::<NaiveField>("rrr..\n").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::RowWidth { found: 5, expected: 6 });
+    }
+
+    #[test]
+    fn trigger() {
+        // 赤を 3 列目に縦に置けば赤が消えて、落ちた青がつながる
+        let seeds = parse_seeds::<NaiveField>(SEEDS).unwrap();
+        let next = NaiveNextPuyo::from_vec(vec![[2, 2]]).unwrap();
+        let (best, chain) = best_trigger(&seeds[0].field, &next, &Rules::FEVER, &SolveOptions::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(chain, 2);
+        assert_eq!(field::chain_with(&mut best.clone(), &Rules::FEVER), 2);
+
+        let next = NaiveNextPuyo::from_vec(vec![[5, 5]]).unwrap();
+        let none = best_trigger(&seeds[0].field, &next, &Rules::FEVER, &SolveOptions::default()).unwrap();
+        assert_eq!(none, None);
+    }
+}
@@ -1,211 +1,273 @@
-use std::collections::VecDeque;
+use crate::cell::{Cell, Color, InvalidCell};
+use crate::rules::{HiddenRow, OjamaClear, Rules};
 
 pub trait Field {
+    /// 列の数
+    const WIDTH: usize = 6;
+    /// 段の数 (一番上の 0 段目は見えない段)
+    const HEIGHT: usize = 13;
+
     fn new() -> Self
     where
         Self: Sized;
-    fn from_u8(value: [[u8; 13]; 6]) -> Self;
-    fn from_char(value: [[char; 13]; 6]) -> Self;
+    /// value[x][y] の並び。大きさが WIDTH x HEIGHT でなければ panic する
+    fn from_cells<C: AsRef<[Cell]>>(value: impl AsRef<[C]>) -> Self
+    where
+        Self: Sized,
+    {
+        let value = value.as_ref();
+        assert_eq!(value.len(), Self::WIDTH, "expected {} columns", Self::WIDTH);
+        let mut res = Self::new();
+        for (x, column) in value.iter().enumerate() {
+            let column = column.as_ref();
+            assert_eq!(column.len(), Self::HEIGHT, "expected {} rows", Self::HEIGHT);
+            for (y, &cell) in column.iter().enumerate() {
+                res.set(y, x, cell);
+            }
+        }
+        res
+    }
+    /// 番号 (Cell::code) の盤面から作る。0-7 以外があれば Err
+    fn from_u8<C: AsRef<[u8]>>(value: impl AsRef<[C]>) -> Result<Self, InvalidCell>
+    where
+        Self: Sized,
+    {
+        let cells = value
+            .as_ref()
+            .iter()
+            .map(|column| column.as_ref().iter().map(|&c| Cell::from_code(c)).collect())
+            .collect::<Result<Vec<Vec<Cell>>, _>>()?;
+        Ok(Self::from_cells(cells))
+    }
+    /// 知らない文字は空白
+    fn from_char<C: AsRef<[char]>>(value: impl AsRef<[C]>) -> Self
+    where
+        Self: Sized,
+    {
+        let cells: Vec<Vec<Cell>> = value
+            .as_ref()
+            .iter()
+            .map(|column| column.as_ref().iter().map(|&c| Cell::from_char(c).unwrap_or(Cell::Empty)).collect())
+            .collect();
+        Self::from_cells(cells)
+    }
     // fn from(v: [[u32; 13]; 6]) -> Self;
-    fn set(&mut self, y: usize, x: usize, v: u8);
-    fn get(&self, y: usize, x: usize) -> u8;
+    fn set(&mut self, y: usize, x: usize, cell: Cell);
+    fn get(&self, y: usize, x: usize) -> Cell;
     // todo? field::fall() に切り出すべき？
-    fn fall(&mut self);
+    /// 浮いているぷよを落とす。一番長く落ちたぷよが何段落ちたかを返す
+    fn fall(&mut self) -> usize;
     //fn chain(&mut self) -> u32;
     fn is_empty(&self) -> bool;
-    fn is_alive(&self) -> bool;
+    /// ぷよぷよ通の窒息点が空いているか。ほかのルールでは is_alive_with を使う
+    fn is_alive(&self) -> bool
+    where
+        Self: Sized,
+    {
+        is_alive_with(self, &Rules::TSU)
+    }
 }
 
 /// 一番上にあるぷよのインデックス
 /// None => 列が空
-/// ✟伝家の宝刀 人力二分探索✟
+/// ぷよは下に詰まっているので二分探索できる
 /// TODO ぷよを置ける位置に変更した方がよい？
 pub fn get_top<F: Field>(field: &F, x: usize) -> Option<usize> {
-    if field.get(12, x) == 0 {
+    let bottom = F::HEIGHT - 1;
+    if field.get(bottom, x).is_empty() {
         return None;
     }
-    if field.get(6, x) != 0 {
-        if field.get(3, x) != 0 {
-            if field.get(1, x) != 0 {
-                if field.get(0, x) != 0 {
-                    return Some(0);
-                } else {
-                    return Some(1);
-                }
-            } else {
-                if field.get(2, x) != 0 {
-                    return Some(2);
-                } else {
-                    return Some(3);
-                }
-            }
+    // lo より上は空、hi は埋まっている
+    let (mut lo, mut hi) = (0, bottom);
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        if field.get(mid, x).is_empty() {
+            lo = mid + 1;
         } else {
-            if field.get(4, x) != 0 {
-                return Some(4);
-            } else if field.get(5, x) != 0 {
-                return Some(5);
-            } else {
-                return Some(6);
-            }
+            hi = mid;
         }
     }
+    Some(lo)
+}
 
-    if field.get(9, x) != 0 {
-        if field.get(7, x) != 0 {
-            return Some(7);
-        } else if field.get(8, x) != 0 {
-            return Some(8);
-        } else {
-            return Some(9);
+/// ツモを置く列を調べる順 (真ん中寄りから)。6 列より広い盤面では右の列を後ろに足す
+pub(crate) fn column_order<F: Field>() -> impl DoubleEndedIterator<Item = usize> {
+    [2, 4, 3, 5, 1, 0]
+        .into_iter()
+        .filter(|&j| j < F::WIDTH)
+        .chain(6..F::WIDTH)
+}
+
+/// ぷよぷよ通のルールで連鎖させて、連鎖数を返す
+pub fn chain<F: Field + Clone>(field: &mut F) -> u32 {
+    chain_with(field, &Rules::TSU)
+}
+
+/// rules で連鎖させて、連鎖数を返す
+pub fn chain_with<F: Field + Clone>(field: &mut F, rules: &Rules) -> u32 {
+    let mut chain_count = 0u32;
+    while let Some((next_field, _)) = pop_groups(field, rules) {
+        chain_count += 1;
+        *field = next_field;
+        field.fall();
+    }
+    chain_count
+}
+
+/// 1 回の消去で消えたグループ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoppedGroup {
+    pub color: Color,
+    /// (段, 列)
+    pub cells: Vec<(usize, usize)>,
+}
+
+/// 盤面のマスの集合
+/// 128 マス以下 (6x13 は 78 マス) なら u128 1 個に収まるのでヒープを使わない
+pub(crate) struct CellSet {
+    width: usize,
+    head: u128,
+    tail: Vec<u128>,
+}
+
+impl CellSet {
+    pub(crate) fn new<F: Field>() -> Self {
+        let cells = F::WIDTH * F::HEIGHT;
+        CellSet {
+            width: F::WIDTH,
+            head: 0,
+            tail: vec![0; cells.saturating_sub(1) / 128],
         }
     }
 
-    if field.get(10, x) != 0 {
-        return Some(10);
+    fn word(&mut self, i: usize) -> &mut u128 {
+        match i / 128 {
+            0 => &mut self.head,
+            w => &mut self.tail[w - 1],
+        }
     }
 
-    if field.get(11, x) != 0 {
-        return Some(11);
+    /// 入っていなければ入れて true
+    #[inline]
+    pub(crate) fn insert(&mut self, y: usize, x: usize) -> bool {
+        let i = y * self.width + x;
+        let word = self.word(i);
+        let bit = 1u128 << (i % 128);
+        let inserted = *word & bit == 0;
+        *word |= bit;
+        inserted
     }
 
-    Some(12)
+    fn iter(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        std::iter::once(&self.head)
+            .chain(self.tail.iter())
+            .enumerate()
+            .flat_map(|(w, &word)| (0..128).filter(move |b| word >> b & 1 == 1).map(move |b| w * 128 + b))
+            .map(|i| (i / self.width, i % self.width))
+    }
 }
 
-pub fn chain<F: Field>(field: &mut F) -> u32
-where
-    F: Clone,
-{
-    let mut chain_count = 0u32;
-    let dy = [0i32, 1, 0, -1];
-    let dx = [1i32, 0, -1, 0];
+/// (y, x) と同じ色でつながっているマスを vis に入れながら connected に集める
+/// 見えない段は数えない
+pub(crate) fn flood<F: Field>(
+    field: &F,
+    rules: &Rules,
+    (y, x): (usize, usize),
+    vis: &mut CellSet,
+    connected: &mut Vec<(usize, usize)>,
+) {
+    let cell = field.get(y, x);
+    connected.clear();
+    connected.push((y, x));
+    let mut i = 0;
+    while let Some(&(y, x)) = connected.get(i) {
+        i += 1;
+        let neighbors = [
+            (y, x + 1),
+            (y + 1, x),
+            (y, x.wrapping_sub(1)),
+            (y.wrapping_sub(1), x),
+        ];
+        for (ny, nx) in neighbors {
+            if nx >= F::WIDTH || ny >= F::HEIGHT || ny < rules.hidden_rows {
+                continue;
+            }
+            if field.get(ny, nx) != cell || !vis.insert(ny, nx) {
+                continue;
+            }
+            connected.push((ny, nx));
+        }
+    }
+}
 
-    loop {
-        let mut chained = false;
-        let mut vis = [[false; 6]; 13];
+/// 消えるグループを全部消して、巻き込まれたおじゃま・固ぷよも処理した盤面 (落とす前) を返す
+/// 消えるグループが無ければ None
+pub fn pop_groups<F: Field + Clone>(field: &F, rules: &Rules) -> Option<(F, Vec<PoppedGroup>)> {
+    let mut groups = vec![];
+    let mut vis = CellSet::new::<F>();
+    // 消えたぷよの隣 (おじゃま・固ぷよが巻き込まれる)。同じ連鎖で何個隣で消えても 1 回
+    let mut hit = CellSet::new::<F>();
+    let mut connected = vec![];
 
-        let mut next_field = field.clone();
+    let mut next_field = field.clone();
 
-        for i in 1..13 {
-            for j in 0..6 {
-                if vis[i][j] {
-                    continue;
-                }
-                vis[i][j] = true;
-                let color = next_field.get(i, j);
-                if color <= 1 {
+    for i in rules.hidden_rows..F::HEIGHT {
+        for j in 0..F::WIDTH {
+            if !vis.insert(i, j) {
+                continue;
+            }
+            let color = match field.get(i, j).color() {
+                Some(color) => color,
+                None => continue,
+            };
+            flood(field, rules, (i, j), &mut vis, &mut connected);
+            if connected.len() < rules.pop_count {
+                continue;
+            }
+            for &(y, x) in connected.iter() {
+                next_field.set(y, x, Cell::Empty);
+                if rules.ojama_clear == OjamaClear::Never {
                     continue;
                 }
-                let mut que = VecDeque::new();
-                let mut connected = VecDeque::new();
-
-                que.push_back((i, j));
-                connected.push_back((i, j));
-
-                while !que.is_empty() {
-                    let (y, x) = que.pop_front().unwrap();
-                    for k in 0..4 {
-                        let ny = y as i32 + dy[k];
-                        let nx = x as i32 + dx[k];
-                        if nx < 0
-                            || ny < 0
-                            || 6 <= nx
-                            || 13 <= ny
-                            || vis[ny as usize][nx as usize]
-                            || ny == 0
-                        {
-                            continue;
-                        }
-                        let new_color = next_field.get(ny as usize, nx as usize);
-                        if new_color != color || new_color == 0 || new_color == 1 {
-                            continue;
-                        }
-                        vis[ny as usize][nx as usize] = true;
-                        que.push_back((ny as usize, nx as usize));
-                        connected.push_back((ny as usize, nx as usize));
-                    }
-                }
-
-                if connected.len() >= 4 {
-                    chained = true;
-                    for (y, x) in connected {
-                        next_field.set(y, x, 0);
-                        for k in 0..4 {
-                            let ny = y as i32 + dy[k];
-                            let nx = x as i32 + dx[k];
-                            if nx < 0
-                                || ny < 0
-                                || 6 <= nx
-                                || 13 <= ny
-                                || ny == 0
-                                || next_field.get(ny as usize, nx as usize) != 1
-                            {
-                                continue;
-                            }
-                            next_field.set(ny as usize, nx as usize, 0);
-                        }
+                for (ny, nx) in [(y, x + 1), (y + 1, x), (y, x.wrapping_sub(1)), (y.wrapping_sub(1), x)] {
+                    if nx < F::WIDTH && ny < F::HEIGHT && ny >= rules.hidden_rows {
+                        hit.insert(ny, nx);
                     }
                 }
             }
+            groups.push(PoppedGroup {
+                color,
+                cells: connected.clone(),
+            });
         }
-
-        if !chained {
-            break;
-        }
-        chain_count += 1;
-        *field = next_field;
-        field.fall();
     }
 
-    chain_count
+    if groups.is_empty() {
+        return None;
+    }
+    for (y, x) in hit.iter() {
+        next_field.set(y, x, next_field.get(y, x).hit());
+    }
+    Some((next_field, groups))
 }
 
+/// ぷよぷよ通のルールで消えるところがあるか
 pub fn has_chain<F: Field>(field: &F) -> bool {
-    let dy = [0i32, 1, 0, -1];
-    let dx = [1i32, 0, -1, 0];
+    has_chain_with(field, &Rules::TSU)
+}
 
-    let mut vis = [[false; 6]; 13];
+/// rules で消えるところがあるか
+pub fn has_chain_with<F: Field>(field: &F, rules: &Rules) -> bool {
+    let mut vis = CellSet::new::<F>();
+    let mut connected = vec![];
 
-    for i in 1..13 {
-        for j in 0..6 {
-            if vis[i][j] {
+    for i in rules.hidden_rows..F::HEIGHT {
+        for j in 0..F::WIDTH {
+            if !vis.insert(i, j) || field.get(i, j).color().is_none() {
                 continue;
             }
-            vis[i][j] = true;
-            let color = field.get(i, j);
-            if color == 0 || color == 1 {
-                continue;
-            }
-            let mut que = VecDeque::new();
-            let mut connected = VecDeque::new();
-
-            que.push_back((i, j));
-            connected.push_back((i, j));
-
-            while !que.is_empty() {
-                let (y, x) = que.pop_front().unwrap();
-                for k in 0..4 {
-                    let ny = y as i32 + dy[k];
-                    let nx = x as i32 + dx[k];
-                    if nx < 0
-                        || ny < 0
-                        || 6 <= nx
-                        || 13 <= ny
-                        || vis[ny as usize][nx as usize]
-                        || ny == 0
-                    {
-                        continue;
-                    }
-                    let new_color = field.get(ny as usize, nx as usize);
-                    if new_color != color || new_color == 0 || new_color == 1 {
-                        continue;
-                    }
-                    vis[ny as usize][nx as usize] = true;
-                    que.push_back((ny as usize, nx as usize));
-                    connected.push_back((ny as usize, nx as usize));
-                }
-            }
-
-            if connected.len() >= 4 {
+            flood(field, rules, (i, j), &mut vis, &mut connected);
+            if connected.len() >= rules.pop_count {
                 return true;
             }
         }
@@ -214,6 +276,31 @@ pub fn has_chain<F: Field>(field: &F) -> bool {
     false
 }
 
+/// rules で (y, x) に落ちてきたぷよ。見えない段に置いたぷよが消えるルールなら空になる
+pub(crate) fn landing(rules: &Rules, y: usize, cell: Cell) -> Cell {
+    if y < rules.hidden_rows && rules.hidden_row == HiddenRow::Vanish {
+        Cell::Empty
+    } else {
+        cell
+    }
+}
+
+/// x 列に子ぷよを下にして置けるか
+/// クイックターンが無いと、両隣の一番上の見える段が埋まっている列では回せない
+pub(crate) fn can_flip<F: Field>(field: &F, rules: &Rules, x: usize) -> bool {
+    if rules.quick_turn {
+        return true;
+    }
+    let y = rules.hidden_rows.min(F::HEIGHT - 1);
+    let open = |c: usize| field.get(y, c).is_empty();
+    (x > 0 && open(x - 1)) || (x + 1 < F::WIDTH && open(x + 1))
+}
+
+/// rules の窒息点が空いているか
+pub fn is_alive_with<F: Field>(field: &F, rules: &Rules) -> bool {
+    let (y, x) = rules.death_cell;
+    field.get(y, x).is_empty()
+}
 
 #[allow(unused)]
 pub fn kenny_bench<T>() where T: Field + Clone {
@@ -230,7 +317,7 @@ pub fn kenny_bench<T>() where T: Field + Clone {
             [3u8, 6, 3, 3, 6, 3, 6, 6, 5, 4, 6, 6, 6],
             [3u8, 4, 3, 4, 4, 4, 3, 3, 4, 4, 5, 5, 5],
         ];
-        let field = T::from_u8(kenny);
+        let field = T::from_u8(kenny).unwrap();
         fields.push(field);
 
     }
@@ -1,103 +1,69 @@
+use crate::render::{Renderer, Style};
 use std::fmt;
+use crate::cell::Cell;
 use crate::field::Field;
 
+/// W 列 H 段の盤面を 1 次元 (y + x * H) で持つ
+/// [u8; W * H] はまだ書けないので列の配列を並べて平らに見る
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
-pub struct Field1D {
-    value: [u8; 78],
+pub struct Field1DOf<const W: usize = 6, const H: usize = 13> {
+    value: [[u8; H]; W],
 }
 
-impl fmt::Display for Field1D {
+/// ふつうの 6 列 13 段
+pub type Field1D = Field1DOf<6, 13>;
+
+impl<const W: usize, const H: usize> fmt::Display for Field1DOf<W, H> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for i in 0..13 {
-            for j in 0..6 {
-                write!(f, "{:2} ", self.get(i, j))?;
-            }
-            writeln!(f)?;
-        }
-        Ok(())
+        f.write_str(&Renderer::new(Style::Ascii).render(self))
     }
 }
 
-impl Field for Field1D {
+impl<const W: usize, const H: usize> Field for Field1DOf<W, H> {
+    const WIDTH: usize = W;
+    const HEIGHT: usize = H;
+
     fn new() -> Self
         where
             Self: Sized {
-        Self { value: [0; 78] }
-    }
-
-    fn from_u8(value: [[u8; 13]; 6]) -> Self {
-        let mut v = Vec::with_capacity(78);
-        for j in 0..6 {
-            for i in 0..13 {
-                v.push(value[j][i]);
-            }
-        }
-
-        Self {
-            value: v.try_into().expect("failed to initialize from u8")
-        }
-    }
-
-    fn from_char(value: [[char; 13]; 6]) -> Self {
-        use std::collections::HashMap;
-        let mut map = HashMap::new();
-        map.insert(&'@', &1u8);
-        map.insert(&'r', &2);
-        map.insert(&'b', &3);
-        map.insert(&'g', &4);
-        map.insert(&'y', &5);
-        map.insert(&'p', &6);
-        let mut res = [[0u8; 13]; 6];
-        for j in 0..6 {
-            for i in 0..13 {
-                if map.contains_key(&value[j][i]) {
-                    res[j][i] = **map.get(&value[j][i]).unwrap();
-                } else {
-                    res[j][i] = 0;
-                }
-            }
-        }
-
-        Self::from_u8(res)
-    }
-
-    #[inline]
-    fn get(&self, y: usize, x: usize) -> u8 {
-        self.value[y + x*13]
+        Self { value: [[0; H]; W] }
     }
 
     #[inline]
-    fn set(&mut self, y: usize, x: usize, v: u8) {
-        self.value[y + x*13] = v;
+    fn get(&self, y: usize, x: usize) -> Cell {
+        Cell::decode(self.value.as_flattened()[y + x*H])
     }
 
     #[inline]
-    fn is_alive(&self) -> bool {
-        self.value[27] == 0
+    fn set(&mut self, y: usize, x: usize, cell: Cell) {
+        self.value.as_flattened_mut()[y + x*H] = cell.code();
     }
 
     #[inline]
     fn is_empty(&self) -> bool {
-        !self.value.iter().any(|&x| x != 0)
+        !self.value.as_flattened().iter().any(|&x| x != 0)
     }
 
-    fn fall(&mut self) {
-        for j in 0..6 {
+    fn fall(&mut self) -> usize {
+        let mut distance = 0;
+        for j in 0..W {
             let mut space = 0usize;
-            for i in (0..13).rev() {
-                if self.get(i, j) == 0 {
+            for i in (0..H).rev() {
+                if self.value.as_flattened()[i + j*H] == 0 {
                     space += 1;
                     continue;
                 }
                 if space == 0 {
                     continue;
                 }
+                distance = distance.max(space);
                 let a = self.get(i, j);
                 let b = self.get(i + space, j);
                 self.set(i, j, b);
                 self.set(i + space, j, a);
             }
         }
+        distance
     }
 
     
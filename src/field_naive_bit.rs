@@ -1,99 +1,70 @@
+use crate::cell::Cell;
 use crate::field::Field;
+use crate::render::{Renderer, Style};
 use std::fmt;
-use colored::*;
 
+/// W 列 H 段の盤面。1 列を u64 に 3 bit ずつ詰めるので H は 21 まで
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
-pub struct FieldNaiveBit {
-    value: [u64; 6],
+pub struct FieldNaiveBitOf<const W: usize = 6, const H: usize = 13> {
+    value: [u64; W],
 }
 
-impl fmt::Display for FieldNaiveBit {
+/// ふつうの 6 列 13 段
+pub type FieldNaiveBit = FieldNaiveBitOf<6, 13>;
+
+impl<const W: usize, const H: usize> FieldNaiveBitOf<W, H> {
+    /// 1 列が u64 に収まるか (コンパイル時に確かめる)
+    const FITS: () = assert!(H * 3 <= 64, "FieldNaiveBitOf holds at most 21 rows");
+}
+
+impl<const W: usize, const H: usize> fmt::Display for FieldNaiveBitOf<W, H> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for i in 0..13 {
-            for j in 0..6 {
-                let s = match self.get(i, j) {
-                    1 => "@".color("white"),
-                    2 => "2".color("red"),
-                    3 => "3".color("blue"),
-                    4 => "4".color("green"),
-                    5 => "5".color("yellow"),
-                    6 => "6".color("magenta"),
-                    _ => "-".color("white"),
-                };
-                write!(f, "{:2} ", s)?;
-            }
-            writeln!(f)?;
-        }
-        Ok(())
+        f.write_str(&Renderer::new(Style::Ascii).render(self))
     }
 }
 
-impl Field for FieldNaiveBit {
+impl<const W: usize, const H: usize> Field for FieldNaiveBitOf<W, H> {
+    const WIDTH: usize = W;
+    const HEIGHT: usize = H;
+
     fn new() -> Self
     where
         Self: Sized,
     {
-        Self {
-            value: [0, 0, 0, 0, 0, 0],
-        }
-    }
-
-    fn from_u8(value: [[u8; 13]; 6]) -> Self {
-        let mut res = Self::new();
-        for j in 0..6 {
-            for i in 0..13 {
-                res.set(i, j, value[j][i]);
-            }
-        }
-
-        res
-    }
-
-    fn from_char(value: [[char; 13]; 6]) -> Self {
-        use std::collections::HashMap;
-        let mut map = HashMap::new();
-        map.insert(&'@', &1u8);
-        map.insert(&'r', &2);
-        map.insert(&'b', &3);
-        map.insert(&'g', &4);
-        map.insert(&'y', &5);
-        map.insert(&'p', &6);
-        let mut res = [[0u8; 13]; 6];
-        for j in 0..6 {
-            for i in 0..13 {
-                if map.contains_key(&value[j][i]) {
-                    res[j][i] = **map.get(&value[j][i]).unwrap();
-                } else {
-                    res[j][i] = 0;
-                }
-            }
-        }
-
-        Self::from_u8(res)
+        #[allow(clippy::let_unit_value)]
+        let () = Self::FITS;
+        Self { value: [0; W] }
     }
 
+    /// Cell の番号は 0-7 なので 3 bit にそのまま入る
     #[inline]
-    fn set(&mut self, y: usize, x: usize, v: u8) {
+    fn set(&mut self, y: usize, x: usize, cell: Cell) {
         let mask = 7u64 << (y * 3);
-        self.value[x] = self.value[x] & !mask | ((v as u64) << (y * 3));
+        self.value[x] = self.value[x] & !mask | ((cell.code() as u64) << (y * 3));
     }
 
     #[inline]
-    fn get(&self, y: usize, x: usize) -> u8 {
+    fn get(&self, y: usize, x: usize) -> Cell {
         let mask = 7u64 << (y * 3);
-        ((self.value[x] & mask) >> (y * 3)) as u8
+        Cell::decode(((self.value[x] & mask) >> (y * 3)) as u8)
     }
 
     #[inline]
-    fn fall(&mut self) {
-        for j in 0..6 {
+    fn fall(&mut self) -> usize {
+        let mut distance = 0;
+        for j in 0..W {
             if self.value[j] == 0 {
                 continue;
             }
             let mut res = 0u64;
             let mut mask = 0b111u64;
-            for _ in 0..13 {
+            // 一番上のぷよより下にある空白の数 = この列で一番長く落ちる距離
+            let mut space = 0;
+            for _ in 0..H {
                 if self.value[j] & mask == 0 {
+                    if res != 0 {
+                        space += 1;
+                    }
                     res <<= 3;
                 } else {
                     res |= self.value[j] & mask;
@@ -101,12 +72,9 @@ impl Field for FieldNaiveBit {
                 mask <<= 3;
             }
             self.value[j] = res;
+            distance = distance.max(space);
         }
-    }
-
-    #[inline]
-    fn is_alive(&self) -> bool {
-        (self.value[2] & 0b111000) == 0
+        distance
     }
 
     #[inline]
@@ -118,46 +86,56 @@ impl Field for FieldNaiveBit {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cell::InvalidCell;
     #[test]
     fn get_test() {
         let mut field = FieldNaiveBit::new();
         field.value[0] = 0b_100_011_010_001_000_111_110_101_100_011_010_001_000;
         println!("{}", field.value[0]);
-        assert_eq!(field.get(0, 0), 0);
-        assert_eq!(field.get(1, 0), 1);
-        assert_eq!(field.get(2, 0), 2);
-        assert_eq!(field.get(3, 0), 3);
-        assert_eq!(field.get(4, 0), 4);
-        assert_eq!(field.get(5, 0), 5);
-        assert_eq!(field.get(6, 0), 6);
-        assert_eq!(field.get(7, 0), 7);
-        assert_eq!(field.get(8, 0), 0);
-        assert_eq!(field.get(9, 0), 1);
-        assert_eq!(field.get(10, 0), 2);
-        assert_eq!(field.get(11, 0), 3);
-        assert_eq!(field.get(12, 0), 4);
+        assert_eq!(field.get(0, 0), Cell::Empty);
+        assert_eq!(field.get(1, 0), Cell::Ojama);
+        assert_eq!(field.get(2, 0), Cell::RED);
+        assert_eq!(field.get(3, 0), Cell::BLUE);
+        assert_eq!(field.get(4, 0), Cell::GREEN);
+        assert_eq!(field.get(5, 0), Cell::YELLOW);
+        assert_eq!(field.get(6, 0), Cell::PURPLE);
+        assert_eq!(field.get(7, 0), Cell::Hard);
+        assert_eq!(field.get(8, 0), Cell::Empty);
+        assert_eq!(field.get(9, 0), Cell::Ojama);
+        assert_eq!(field.get(10, 0), Cell::RED);
+        assert_eq!(field.get(11, 0), Cell::BLUE);
+        assert_eq!(field.get(12, 0), Cell::GREEN);
     }
 
     #[test]
     fn set_test() {
         let mut field = FieldNaiveBit::new();
         for i in 0..13 {
-            field.set(i, 0, (i % 8) as u8);
+            field.set(i, 0, Cell::from_code((i % 8) as u8).unwrap());
         }
         println!("{}", field.value[0]);
-        assert_eq!(field.get(0, 0), 0);
-        assert_eq!(field.get(1, 0), 1);
-        assert_eq!(field.get(2, 0), 2);
-        assert_eq!(field.get(3, 0), 3);
-        assert_eq!(field.get(4, 0), 4);
-        assert_eq!(field.get(5, 0), 5);
-        assert_eq!(field.get(6, 0), 6);
-        assert_eq!(field.get(7, 0), 7);
-        assert_eq!(field.get(8, 0), 0);
-        assert_eq!(field.get(9, 0), 1);
-        assert_eq!(field.get(10, 0), 2);
-        assert_eq!(field.get(11, 0), 3);
-        assert_eq!(field.get(12, 0), 4);
+        assert_eq!(field.get(0, 0), Cell::Empty);
+        assert_eq!(field.get(1, 0), Cell::Ojama);
+        assert_eq!(field.get(2, 0), Cell::RED);
+        assert_eq!(field.get(3, 0), Cell::BLUE);
+        assert_eq!(field.get(4, 0), Cell::GREEN);
+        assert_eq!(field.get(5, 0), Cell::YELLOW);
+        assert_eq!(field.get(6, 0), Cell::PURPLE);
+        assert_eq!(field.get(7, 0), Cell::Hard);
+        assert_eq!(field.get(8, 0), Cell::Empty);
+        assert_eq!(field.get(9, 0), Cell::Ojama);
+        assert_eq!(field.get(10, 0), Cell::RED);
+        assert_eq!(field.get(11, 0), Cell::BLUE);
+        assert_eq!(field.get(12, 0), Cell::GREEN);
+    }
+
+    #[test]
+    fn from_u8_rejects_unknown_code() {
+        let mut value = [[0u8; 13]; 6];
+        value[3][12] = 7;
+        assert_eq!(FieldNaiveBit::from_u8(value).unwrap().get(12, 3), Cell::Hard);
+        value[3][12] = 8;
+        assert_eq!(FieldNaiveBit::from_u8(value), Err(InvalidCell(8)));
     }
 
     #[test]
@@ -0,0 +1,238 @@
+//! 「K 手で N 連鎖」のなぞぷよを作る
+//!
+//! 1. 1 連鎖 (4 個) から始めて、4 個ずつ差し込んで連鎖を後ろから組む
+//!    差し込んだ 4 個が消えれば一つ前の盤面にそのまま戻るので、連鎖数は 1 ずつ増える
+//! 2. 上から K 組 (2K 個) 取ってネクストにする
+//! 3. ソルバーで解が 1 つだけか確かめる。だめならやり直し
+
+use std::fmt::Display;
+use std::hash::Hash;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::cell::Cell;
+use crate::field::{self, Field};
+use crate::naive_next_puyo::NaiveNextPuyo;
+use crate::nazopuyo_info::NazopuyoInfo;
+use crate::nazopuyo_solver::Solver;
+
+/// 列ごとの高さの上限 (3 列目は 12 段目が埋まると窒息なので 11)
+const MAX_HEIGHT: [usize; 6] = [12, 12, 11, 12, 12, 12];
+
+/// 1 段差し込むのを何回まで試すか
+const INSERT_TRIES: usize = 200;
+
+/// 列ごとに下から並べた盤面
+type Columns = [Vec<u8>; 6];
+
+#[derive(Debug)]
+pub struct Generator {
+    chain: u32,
+    pieces: usize,
+    colors: u8,
+    max_attempts: usize,
+    rng: StdRng,
+}
+
+impl Generator {
+    /// chain 連鎖, pieces 手, colors 色 (2-5) の問題を作る
+    pub fn new(chain: u32, pieces: usize, colors: u8, seed: u64) -> Self {
+        assert!(chain >= 1, "chain must be at least 1");
+        assert!((1..=10).contains(&pieces), "pieces must be 1-10");
+        assert!((2..=5).contains(&colors), "colors must be 2-5");
+        Self {
+            chain,
+            pieces,
+            colors,
+            max_attempts: 1000,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// 何回やり直したらあきらめるか
+    pub fn max_attempts(mut self, n: usize) -> Self {
+        self.max_attempts = n;
+        self
+    }
+
+    /// 解がちょうど 1 つの問題を作る。max_attempts 回やってだめなら None
+    /// 同じ seed なら同じ問題の列が出てくる
+    /// 連鎖は左 6 列・下 13 段に組むので、それより小さい盤面では panic する
+    pub fn generate<F>(&mut self) -> Option<(F, NazopuyoInfo)>
+    where
+        F: Field + Clone + Eq + Hash + Display + Send + 'static,
+    {
+        assert!(F::WIDTH >= 6 && F::HEIGHT >= 13, "generator needs at least a 6x13 field");
+        for _ in 0..self.max_attempts {
+            let columns = match self.build_chain() {
+                Some(c) => c,
+                None => continue,
+            };
+            let (field, info) = match self.take_pairs::<F>(columns) {
+                Some(p) => p,
+                None => continue,
+            };
+            if Solver::new(field.clone(), info.clone()).count_solutions(2) == 1 {
+                return Some((field, info));
+            }
+        }
+        None
+    }
+
+    /// self.chain 連鎖する盤面を後ろから組む
+    fn build_chain(&mut self) -> Option<Columns> {
+        let mut columns: Columns = Default::default();
+        let mut last_color = 0;
+        for _ in 0..self.chain {
+            let color = loop {
+                let c = self.rng.gen_range(2..2 + self.colors);
+                if c != last_color {
+                    break c;
+                }
+            };
+            columns = (0..INSERT_TRIES).find_map(|_| self.insert_group(&columns, color))?;
+            last_color = color;
+        }
+        Some(columns)
+    }
+
+    /// 4 個の color をどこかに差し込む
+    /// 差し込んだ 4 個だけがつながって消え、他はどこも消えないときだけ Some
+    fn insert_group(&mut self, columns: &Columns, color: u8) -> Option<Columns> {
+        // 4 個を 1-3 列に分ける
+        let width = self.rng.gen_range(1..=3usize);
+        let left = self.rng.gen_range(0..=6 - width);
+        let mut counts = vec![1; width];
+        for _ in width..4 {
+            counts[self.rng.gen_range(0..width)] += 1;
+        }
+        let base = self.rng.gen_range(0..=columns.iter().map(|c| c.len()).max().unwrap_or(0));
+
+        let mut res = columns.clone();
+        let mut inserted = vec![];
+        for (k, &n) in counts.iter().enumerate() {
+            let x = left + k;
+            if res[x].len() + n > MAX_HEIGHT[x] {
+                return None;
+            }
+            let h = base.min(res[x].len());
+            for i in 0..n {
+                res[x].insert(h, color);
+                inserted.push((x, h + i));
+            }
+        }
+
+        let groups = groups(&res);
+        let popping: Vec<&Vec<(usize, usize)>> = groups.iter().filter(|g| g.len() >= 4).collect();
+        match popping[..] {
+            [g] if g.len() == 4 && inserted.iter().all(|c| g.contains(c)) => Some(res),
+            _ => None,
+        }
+    }
+
+    /// 上から 2 * pieces 個取ってネクストにする。残りの盤面が消えてしまうなら None
+    fn take_pairs<F: Field>(&mut self, mut columns: Columns) -> Option<(F, NazopuyoInfo)> {
+        let mut taken = vec![];
+        for _ in 0..self.pieces * 2 {
+            let candidates: Vec<usize> = (0..6).filter(|&x| !columns[x].is_empty()).collect();
+            if candidates.is_empty() {
+                return None;
+            }
+            let x = candidates[self.rng.gen_range(0..candidates.len())];
+            taken.push(columns[x].pop().unwrap());
+        }
+        if groups(&columns).iter().any(|g| g.len() >= 4) {
+            return None;
+        }
+
+        // 最後に取ったものから置いていく
+        taken.reverse();
+        let next = NaiveNextPuyo::from_vec(taken.chunks(2).map(|p| [p[0], p[1]]).collect()).ok()?;
+
+        let mut field = F::new();
+        for (x, column) in columns.iter().enumerate() {
+            for (h, &color) in column.iter().enumerate() {
+                field.set(F::HEIGHT - 1 - h, x, Cell::decode(color));
+            }
+        }
+        debug_assert!(!field::has_chain(&field));
+        Some((
+            field,
+            NazopuyoInfo::new(self.chain, next),
+        ))
+    }
+}
+
+/// 同じ色でつながっているまとまり (列, 高さ)
+fn groups(columns: &Columns) -> Vec<Vec<(usize, usize)>> {
+    let mut seen: [[bool; 13]; 6] = [[false; 13]; 6];
+    let mut res = vec![];
+    for x in 0..6 {
+        for h in 0..columns[x].len() {
+            if seen[x][h] {
+                continue;
+            }
+            let color = columns[x][h];
+            seen[x][h] = true;
+            let mut group = vec![(x, h)];
+            let mut stack = vec![(x, h)];
+            while let Some((cx, ch)) = stack.pop() {
+                let neighbors = [
+                    (cx.wrapping_sub(1), ch),
+                    (cx + 1, ch),
+                    (cx, ch.wrapping_sub(1)),
+                    (cx, ch + 1),
+                ];
+                for (nx, nh) in neighbors {
+                    if nx < 6 && nh < columns[nx].len() && !seen[nx][nh] && columns[nx][nh] == color {
+                        seen[nx][nh] = true;
+                        group.push((nx, nh));
+                        stack.push((nx, nh));
+                    }
+                }
+            }
+            res.push(group);
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::field_naive_bit::FieldNaiveBit;
+
+    #[test]
+    fn generates_unique_puzzles() {
+        for (chain, pieces) in [(2, 1), (3, 2)] {
+            let mut generator = Generator::new(chain, pieces, 4, 7);
+            let (field, info) = generator.generate::<FieldNaiveBit>().unwrap();
+            assert_eq!(info.chain, chain);
+            assert_eq!(info.next.len(), pieces);
+            assert!(!field::has_chain(&field));
+            assert_eq!(Solver::new(field, info).count_solutions(2), 1);
+        }
+    }
+
+    #[test]
+    fn same_seed_same_puzzle() {
+        let a = Generator::new(3, 2, 3, 42).generate::<FieldNaiveBit>().unwrap();
+        let b = Generator::new(3, 2, 3, 42).generate::<FieldNaiveBit>().unwrap();
+        assert_eq!(a.0, b.0);
+        assert_eq!(a.1.next.pairs(), b.1.next.pairs());
+    }
+
+    #[test]
+    fn build_chain_fires() {
+        let mut generator = Generator::new(6, 1, 4, 1);
+        let columns = generator.build_chain().unwrap();
+        let mut field = FieldNaiveBit::new();
+        for (x, column) in columns.iter().enumerate() {
+            for (h, &color) in column.iter().enumerate() {
+                field.set(12 - h, x, Cell::decode(color));
+            }
+        }
+        assert_eq!(field::chain(&mut field), 6);
+    }
+}
@@ -0,0 +1,436 @@
+//! 解答をアニメーション GIF にする
+//!
+//! 1 手ずつ置いて、最後に field::chain と同じように 1 連鎖ずつ消して落とす様子を 1 コマずつ描く
+//! ぷよの絵は assets/sprites.txt (16x16 が 3 つ: 色ぷよ, おじゃま, 固ぷよ) を埋め込んで使う
+//! コマの長さは timing の FrameCosts から決める
+//!
+//! スプライトの文字は `.` 透明、`o` 縁、`#` 塗り (ぷよの色)、`+` 光
+
+use std::collections::HashMap;
+
+use crate::cell::{Cell, Color};
+use crate::chain_frames::{chain_frames, FrameKind};
+use crate::field::Field;
+use crate::nazopuyo_info::NazopuyoInfo;
+use crate::placement::{play, IllegalMove, Placement};
+use crate::render::placed_cells;
+use crate::timing::{fall_frames, placement_timing, FrameCosts};
+
+const SPRITES: &str = include_str!("../assets/sprites.txt");
+/// 1 マスの大きさ (px)
+const SIZE: usize = 16;
+/// 最後のコマを止めておく時間 (1/100 秒)
+const HOLD: u16 = 100;
+
+/// パレットの番号
+const BACKGROUND: u8 = 0;
+const HIDDEN_ROW: u8 = 1;
+const OUTLINE: u8 = 2;
+const WHITE: u8 = 3;
+const OJAMA: u8 = 9;
+const HARD: u8 = 10;
+
+/// 16 色 (RGB)
+const PALETTE: [[u8; 3]; 16] = [
+    [0x21, 0x21, 0x21],
+    [0x42, 0x42, 0x42],
+    [0x10, 0x10, 0x10],
+    [0xff, 0xff, 0xff],
+    [0xe5, 0x39, 0x35],
+    [0x1e, 0x88, 0xe5],
+    [0x43, 0xa0, 0x47],
+    [0xfd, 0xd8, 0x35],
+    [0x8e, 0x24, 0xaa],
+    [0xbd, 0xbd, 0xbd],
+    [0x75, 0x75, 0x75],
+    [0, 0, 0],
+    [0, 0, 0],
+    [0, 0, 0],
+    [0, 0, 0],
+    [0, 0, 0],
+];
+
+fn color_index(color: Color) -> u8 {
+    4 + color as u8
+}
+
+/// 埋め込みのスプライトシートの i 番目
+fn sprite(i: usize) -> impl Iterator<Item = (usize, usize, char)> {
+    SPRITES.lines().enumerate().flat_map(move |(y, line)| {
+        line.chars()
+            .skip(i * SIZE)
+            .take(SIZE)
+            .enumerate()
+            .map(move |(x, c)| (y, x, c))
+    })
+}
+
+/// 盤面 1 コマ分の画像 (パレットの番号を左上から並べたもの)
+struct Canvas {
+    width: usize,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new<F: Field>() -> Self {
+        let width = F::WIDTH * SIZE;
+        let mut pixels = vec![BACKGROUND; width * F::HEIGHT * SIZE];
+        pixels[..width * SIZE].fill(HIDDEN_ROW);
+        Self { width, pixels }
+    }
+
+    /// (y, x) のマスにぷよを描く。ghost なら消えたぷよとして白く塗る
+    fn draw(&mut self, y: usize, x: usize, cell: Cell, highlight: bool, ghost: bool) {
+        let (sheet, fill) = match cell {
+            Cell::Empty => return,
+            Cell::Color(color) => (0, color_index(color)),
+            Cell::Ojama => (1, OJAMA),
+            Cell::Hard => (2, HARD),
+        };
+        let fill = if ghost { WHITE } else { fill };
+        for (dy, dx, c) in sprite(sheet) {
+            let index = match c {
+                'o' if highlight => WHITE,
+                'o' => OUTLINE,
+                '#' => fill,
+                '+' => WHITE,
+                _ => continue,
+            };
+            self.pixels[(y * SIZE + dy) * self.width + x * SIZE + dx] = index;
+        }
+    }
+}
+
+fn frame<F: Field>(field: &F, highlight: &[(usize, usize)], ghosts: &[(usize, usize, Cell)]) -> Vec<u8> {
+    let mut canvas = Canvas::new::<F>();
+    for y in 0..F::HEIGHT {
+        for x in 0..F::WIDTH {
+            canvas.draw(y, x, field.get(y, x), highlight.contains(&(y, x)), false);
+        }
+    }
+    for &(y, x, cell) in ghosts {
+        canvas.draw(y, x, cell, false, true);
+    }
+    canvas.pixels
+}
+
+/// ゲームのフレーム数 (60 分の 1 秒) を GIF の単位 (100 分の 1 秒) にする
+fn centis(frames: u32) -> u32 {
+    (frames * 100 + 30) / 60
+}
+
+/// 手順を置いて連鎖させる様子の GIF
+/// コマの長さは timing::sequence_timing と同じ数え方で、最後のコマだけ HOLD 止める
+/// 置けない手があれば (何手目か (0 始まり), 理由)
+pub fn solution_gif<F: Field + Clone>(
+    field: &F,
+    info: &NazopuyoInfo,
+    placements: &[Placement],
+    costs: &FrameCosts,
+) -> Result<Vec<u8>, (usize, IllegalMove)> {
+    // (絵, ゲームのフレーム数)。i 手目を置いている間は置く前の盤面を見せる
+    let mut frames = vec![];
+    let mut current = field.clone();
+    let mut placed = vec![];
+    for (step, &placement) in placements.iter().enumerate() {
+        frames.push((frame(&current, &placed, &[]), placement_timing(&current, placement, costs)));
+        let before = current.clone();
+        play(&mut current, info, step, placement).map_err(|reason| (step, reason))?;
+        placed = placed_cells(&before, &current);
+    }
+    // 置き終わったらすぐ連鎖が始まる
+    frames.push((frame(&current, &placed, &[]), 0));
+
+    for f in chain_frames(&current, &info.rules).skip(1) {
+        let image = match &f.kind {
+            FrameKind::Popped { groups, .. } => {
+                let ghosts: Vec<_> = groups
+                    .iter()
+                    .flat_map(|g| g.cells.iter().map(move |&(y, x)| (y, x, Cell::Color(g.color))))
+                    .collect();
+                (frame(&f.field, &[], &ghosts), costs.pop)
+            }
+            FrameKind::Fell { distance, .. } => (frame(&f.field, &[], &[]), fall_frames(*distance, costs)),
+            FrameKind::Placed => continue,
+        };
+        frames.push(image);
+    }
+
+    // 長さ 0 のコマは最後の 1 枚以外出さない
+    // 丸めの誤差がたまらないように、始まりからの時間で区切る。2 より短いとブラウザが無視するので 2 以上
+    let mut gif = GifWriter::new(F::WIDTH * SIZE, F::HEIGHT * SIZE);
+    let last = frames.len() - 1;
+    let mut elapsed = 0;
+    for (i, (pixels, length)) in frames.iter().enumerate() {
+        if *length == 0 && i != last {
+            continue;
+        }
+        let delay = centis(elapsed + length) - centis(elapsed);
+        elapsed += length;
+        let delay = if i == last {
+            delay as u16 + HOLD
+        } else {
+            delay.clamp(2, u16::MAX as u32) as u16
+        };
+        gif.frame(pixels, delay);
+    }
+    Ok(gif.finish())
+}
+
+/// 16 色パレット固定の GIF89a を書く
+struct GifWriter {
+    width: u16,
+    height: u16,
+    out: Vec<u8>,
+}
+
+impl GifWriter {
+    fn new(width: usize, height: usize) -> Self {
+        let (width, height) = (width as u16, height as u16);
+        let mut out = b"GIF89a".to_vec();
+        out.extend_from_slice(&width.to_le_bytes());
+        out.extend_from_slice(&height.to_le_bytes());
+        // グローバルパレットあり、色の深さ 4 bit、パレット 2^(3+1) 色
+        out.extend_from_slice(&[0b1011_0011, 0, 0]);
+        for rgb in PALETTE.iter() {
+            out.extend_from_slice(rgb);
+        }
+        // ずっと繰り返す
+        out.extend_from_slice(&[0x21, 0xff, 0x0b]);
+        out.extend_from_slice(b"NETSCAPE2.0");
+        out.extend_from_slice(&[0x03, 0x01, 0x00, 0x00, 0x00]);
+        Self { width, height, out }
+    }
+
+    /// delay は 1/100 秒
+    fn frame(&mut self, pixels: &[u8], delay: u16) {
+        self.out.extend_from_slice(&[0x21, 0xf9, 0x04, 0x00]);
+        self.out.extend_from_slice(&delay.to_le_bytes());
+        self.out.extend_from_slice(&[0x00, 0x00]);
+
+        self.out.push(0x2c);
+        self.out.extend_from_slice(&[0, 0, 0, 0]);
+        self.out.extend_from_slice(&self.width.to_le_bytes());
+        self.out.extend_from_slice(&self.height.to_le_bytes());
+        self.out.push(0);
+
+        self.out.push(MIN_CODE_SIZE);
+        for block in lzw(pixels).chunks(255) {
+            self.out.push(block.len() as u8);
+            self.out.extend_from_slice(block);
+        }
+        self.out.push(0);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.out.push(0x3b);
+        self.out
+    }
+}
+
+const MIN_CODE_SIZE: u8 = 4;
+const MAX_CODES: u16 = 4096;
+
+/// 下位ビットから詰める
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u32) {
+        self.acc |= (code as u32) << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.bytes.push(self.acc as u8);
+            self.acc >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.acc as u8);
+        }
+        self.bytes
+    }
+}
+
+/// GIF の LZW 圧縮 (サブブロックに分ける前)
+fn lzw(pixels: &[u8]) -> Vec<u8> {
+    let clear = 1u16 << MIN_CODE_SIZE;
+    let end = clear + 1;
+    let mut dict: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next_code = end + 1;
+    let mut size = MIN_CODE_SIZE as u32 + 1;
+    let mut out = BitWriter::default();
+    out.write(clear, size);
+
+    let mut iter = pixels.iter();
+    let mut prefix = match iter.next() {
+        Some(&p) => p as u16,
+        None => {
+            out.write(end, size);
+            return out.finish();
+        }
+    };
+    for &p in iter {
+        if let Some(&code) = dict.get(&(prefix, p)) {
+            prefix = code;
+            continue;
+        }
+        out.write(prefix, size);
+        if next_code == MAX_CODES {
+            out.write(clear, size);
+            dict.clear();
+            next_code = end + 1;
+            size = MIN_CODE_SIZE as u32 + 1;
+        } else {
+            dict.insert((prefix, p), next_code);
+            // 展開する側は 1 つ遅れて辞書に足すので、足した番号が今のビット数に入らなくなったら増やす
+            if next_code == 1 << size {
+                size += 1;
+            }
+            next_code += 1;
+        }
+        prefix = p as u16;
+    }
+    out.write(prefix, size);
+    out.write(end, size);
+    out.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::naive_field::NaiveField;
+    use crate::placement::parse_placements;
+    use crate::puzzle_text::parse_puzzle;
+    use crate::timing::sequence_timing;
+
+    /// テスト用の LZW 展開
+    fn unlzw(data: &[u8]) -> Vec<u8> {
+        let clear = 1u16 << MIN_CODE_SIZE;
+        let end = clear + 1;
+        let mut table: Vec<Vec<u8>> = vec![];
+        let mut size = 0;
+        let (mut pos, mut prev): (usize, Option<u16>) = (0, None);
+        let mut out = vec![];
+        loop {
+            let mut code = 0u16;
+            for i in 0..size.max(MIN_CODE_SIZE as usize + 1) {
+                let bit = (data[(pos + i) / 8] >> ((pos + i) % 8)) & 1;
+                code |= (bit as u16) << i;
+            }
+            pos += size.max(MIN_CODE_SIZE as usize + 1);
+            if code == clear {
+                table = (0..clear).map(|c| vec![c as u8]).collect();
+                table.push(vec![]);
+                table.push(vec![]);
+                size = MIN_CODE_SIZE as usize + 1;
+                prev = None;
+                continue;
+            }
+            if code == end {
+                return out;
+            }
+            let entry = match (table.get(code as usize), prev) {
+                (Some(e), _) => e.clone(),
+                (None, Some(p)) => {
+                    let mut e = table[p as usize].clone();
+                    e.push(e[0]);
+                    e
+                }
+                (None, None) => panic!("bad code"),
+            };
+            if let Some(p) = prev {
+                if table.len() < MAX_CODES as usize {
+                    let mut e = table[p as usize].clone();
+                    e.push(entry[0]);
+                    table.push(e);
+                    if table.len() == 1 << size && size < 12 {
+                        size += 1;
+                    }
+                }
+            }
+            out.extend_from_slice(&entry);
+            prev = Some(code);
+        }
+    }
+
+    #[test]
+    fn lzw_round_trip() {
+        let flat = vec![3u8; 5000];
+        assert_eq!(unlzw(&lzw(&flat)), flat);
+        // 辞書があふれてクリアするくらいばらばらな並び
+        let noisy: Vec<u8> = (0..20000u32).map(|i| (i.wrapping_mul(2654435761) >> 7) as u8 & 0x0f).collect();
+        assert_eq!(unlzw(&lzw(&noisy)), noisy);
+        assert_eq!(unlzw(&lzw(&[])), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn solution() {
+        // 3 列目に赤を置くと 赤 -> 青 -> 緑 の 3 連鎖。1, 2 連鎖目のあとは 3 段落ちる
+        let text = format!("{}gg....\ngb....\nbr....\nbr....\nbr....\nnext: rg\nchain: 3\n", "......\n".repeat(8));
+        let (field, info): (NaiveField, _) = parse_puzzle(&text).unwrap();
+        let placements = parse_placements("3u").unwrap();
+        let costs = FrameCosts::TSU;
+        let gif = solution_gif(&field, &info, &placements, &costs).unwrap();
+        assert!(gif.starts_with(b"GIF89a"));
+        assert_eq!(&gif[6..10], &[96, 0, 208, 0]);
+        assert_eq!(gif.last(), Some(&0x3b));
+
+        // 置く前, そのあと連鎖ごとに 消えた, 落ちた
+        // 置いた直後は連鎖がすぐ始まるので出さない。最後に落ちたコマは落ちるものが無いので長さ 0
+        let delays: Vec<u32> = gif
+            .windows(6)
+            .filter(|w| w[..3] == [0x21, 0xf9, 0x04])
+            .map(|w| u16::from_le_bytes([w[4], w[5]]) as u32)
+            .collect();
+        let fell = 3 * costs.fall_per_row + costs.landing;
+        let starts = [0, costs.placement, costs.placement + costs.pop, costs.placement + costs.pop + fell];
+        assert_eq!(delays.len(), 7);
+        assert_eq!(delays[..3], [1, 2, 3].map(|i| centis(starts[i]) - centis(starts[i - 1])));
+        assert_eq!(delays[6], HOLD as u32);
+
+        // 全体では timing と同じ長さ
+        let timing = sequence_timing(&field, &info, &placements, &costs).unwrap();
+        assert_eq!(delays.iter().sum::<u32>(), centis(timing.total()) + HOLD as u32);
+
+        let long = parse_placements("3u 1u").unwrap();
+        assert_eq!(solution_gif(&field, &info, &long, &costs), Err((1, IllegalMove::OutOfRange)));
+    }
+
+    #[test]
+    fn split_placement() {
+        // ちぎった手の時間も timing と同じに数える
+        let text = format!("{}.....g\n.....y\n.....g\n.....y\nnext: gy\nchain: 1\n", "......\n".repeat(9));
+        let (field, info): (NaiveField, _) = parse_puzzle(&text).unwrap();
+        let placements = parse_placements("5r").unwrap();
+        let costs = FrameCosts::TSU;
+        let gif = solution_gif(&field, &info, &placements, &costs).unwrap();
+        let delays: Vec<u32> = gif
+            .windows(6)
+            .filter(|w| w[..3] == [0x21, 0xf9, 0x04])
+            .map(|w| u16::from_le_bytes([w[4], w[5]]) as u32)
+            .collect();
+        let timing = sequence_timing(&field, &info, &placements, &costs).unwrap();
+        assert_eq!(timing.placement, costs.placement + 4 * costs.fall_per_row + costs.landing);
+        assert_eq!(delays, [centis(timing.placement), HOLD as u32]);
+    }
+
+    #[test]
+    fn sprites() {
+        assert_eq!(SPRITES.lines().count(), SIZE);
+        assert!(SPRITES.lines().all(|l| l.chars().count() == 3 * SIZE));
+        let mut field = NaiveField::new();
+        field.set(12, 0, Cell::RED);
+        let pixels = frame(&field, &[], &[]);
+        // 左下のマスの目の下が赤で塗られている
+        let below_eyes = (12 * SIZE + 10) * 6 * SIZE + 8;
+        assert_eq!(pixels[below_eyes], color_index(Color::Red));
+        assert_eq!(pixels[0], HIDDEN_ROW);
+    }
+}
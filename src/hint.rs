@@ -0,0 +1,257 @@
+//! 途中まで置いた問題へのヒント
+//!
+//! まだ解けるなら次に置けばよい手を、もう解けないならどの手で解けなくなったかを返す
+
+use std::fmt::{self, Display};
+use std::hash::Hash;
+
+use crate::field::Field;
+use crate::nazopuyo_info::NazopuyoInfo;
+use crate::nazopuyo_solver::Solver;
+use crate::placement::{play, verify, IllegalMove, Placement, Verdict};
+use crate::solve_options::{Budget, GiveUpReason, SolveOptions, SolveResult};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "status", rename_all = "snake_case"))]
+pub enum Hint {
+    /// まだ解ける。次にこう置けば解ける
+    Next { placement: Placement },
+    /// step 手目 (0 始まり) で解けなくなった。その手の代わりに instead と置けば解けた
+    BrokenAt { step: usize, instead: Placement },
+    /// 問題がはじめから解けない
+    Unsolvable,
+    /// 置いた手が置けない
+    Illegal { step: usize, reason: IllegalMove },
+    /// 全部置き終わっている
+    Finished { verdict: Verdict },
+}
+
+impl Display for Hint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Hint::Next { placement } => write!(f, "still solvable, play {}", placement),
+            Hint::BrokenAt { step, instead } => {
+                write!(f, "not solvable since move {}, {} would have kept it solvable", step + 1, instead)
+            }
+            Hint::Unsolvable => write!(f, "the puzzle has no solution"),
+            Hint::Illegal { step, reason } => write!(f, "move {}: {}", step + 1, reason),
+            Hint::Finished { verdict } => write!(f, "all moves played, {}", verdict),
+        }
+    }
+}
+
+/// 途中で何度もソルバーを呼ぶが、打ち切り条件は budget 1 つを分け合う
+struct Hinter<'a> {
+    info: &'a NazopuyoInfo,
+    budget: &'a Budget,
+}
+
+impl Hinter<'_> {
+    /// depth 手置いたあとの field から解けるか
+    fn solvable<F>(&self, field: &F, depth: usize) -> Result<bool, GiveUpReason>
+    where
+        F: Field + Clone + Eq + Hash + Display + Send + 'static,
+    {
+        let mut solver = Solver::from_position(field.clone(), self.info.clone(), depth as u32);
+        match solver.solve_in(self.budget) {
+            SolveResult::Solved(_) => Ok(true),
+            SolveResult::NoSolution => Ok(false),
+            SolveResult::GaveUp(reason) => Err(reason),
+        }
+    }
+
+    /// depth 手置いたあとの field で、解けるままになる次の手
+    fn good_move<F>(&self, field: &F, depth: usize) -> Result<Option<Placement>, GiveUpReason>
+    where
+        F: Field + Clone + Eq + Hash + Display + Send + 'static,
+    {
+        for placement in Placement::all_in(F::WIDTH) {
+            let mut next = field.clone();
+            if play(&mut next, self.info, depth, placement).is_err() {
+                continue;
+            }
+            if self.solvable(&next, depth + 1)? {
+                return Ok(Some(placement));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// played まで置いた局面へのヒント
+/// 探索が options の打ち切り条件に当たったらその理由を返す
+pub fn hint<F>(field: &F, info: &NazopuyoInfo, played: &[Placement], options: &SolveOptions) -> Result<Hint, GiveUpReason>
+where
+    F: Field + Clone + Eq + Hash + Display + Send + 'static,
+{
+    let budget = Budget::new(options, info.next.len());
+    let res = hint_in(field, info, played, &budget);
+    budget.finish();
+    res
+}
+
+fn hint_in<F>(field: &F, info: &NazopuyoInfo, played: &[Placement], budget: &Budget) -> Result<Hint, GiveUpReason>
+where
+    F: Field + Clone + Eq + Hash + Display + Send + 'static,
+{
+    if played.len() >= info.next.len() {
+        return Ok(Hint::Finished {
+            verdict: verify(field, info, played),
+        });
+    }
+
+    // positions[i] は i 手置いたあとの盤面
+    let mut positions = vec![field.clone()];
+    for (step, &placement) in played.iter().enumerate() {
+        let mut next = positions[step].clone();
+        if let Err(reason) = play(&mut next, info, step, placement) {
+            return Ok(Hint::Illegal { step, reason });
+        }
+        positions.push(next);
+    }
+
+    let hinter = Hinter { info, budget };
+    let depth = played.len();
+    if let Some(placement) = hinter.good_move(&positions[depth], depth)? {
+        return Ok(Hint::Next { placement });
+    }
+    if !hinter.solvable(&positions[0], 0)? {
+        return Ok(Hint::Unsolvable);
+    }
+
+    // 解けるかどうかは手を進めるほど 真 -> 偽 にしか変わらないので二分探索する
+    // lo 手目までは解ける、hi 手目では解けない
+    let (mut lo, mut hi) = (0, depth);
+    while hi - lo > 1 {
+        let mid = (lo + hi) / 2;
+        if hinter.solvable(&positions[mid], mid)? {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    // 手の判定はソルバーと同じなので lo で見つかるはず。見つからなければもっと前の手を探す
+    for step in (0..=lo).rev() {
+        if let Some(instead) = hinter.good_move(&positions[step], step)? {
+            return Ok(Hint::BrokenAt { step, instead });
+        }
+    }
+    Ok(Hint::Unsolvable)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    use crate::naive_field::NaiveField;
+    use crate::placement::parse_placements;
+    use crate::progress::{Progress, ProgressObserver};
+    use crate::puzzle_text::parse_puzzle;
+
+    /// 赤 3 つに届くのは 2 列目の 11 段目だけ
+    fn puzzle(next: &str) -> (NaiveField, NazopuyoInfo) {
+        let text = format!("{}y.gbyb\nrrrbyb\nnext: {}\nchain: 1\n", "......\n".repeat(11), next);
+        parse_puzzle(&text).unwrap()
+    }
+
+    fn hint_at(puzzle: &(NaiveField, NazopuyoInfo), played: &str) -> Hint {
+        let played = parse_placements(played).unwrap();
+        hint(&puzzle.0, &puzzle.1, &played, &SolveOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn next_move() {
+        let p = puzzle("gg rb");
+        let first = match hint_at(&p, "") {
+            Hint::Next { placement } => placement,
+            h => panic!("{:?}", h),
+        };
+        let second = match hint_at(&p, &first.to_string()) {
+            Hint::Next { placement } => placement,
+            h => panic!("{:?}", h),
+        };
+        assert_eq!(
+            hint_at(&p, &format!("{} {}", first, second)),
+            Hint::Finished {
+                verdict: Verdict::Cleared { chain: 1 }
+            }
+        );
+    }
+
+    #[test]
+    fn broken_move() {
+        let p = puzzle("gg rb");
+        // 1 手目で 2 列目をふさいだ
+        let instead = match hint_at(&p, "2u") {
+            Hint::BrokenAt { step: 0, instead } => instead,
+            h => panic!("{:?}", h),
+        };
+        assert_ne!(instead.x, 1);
+        assert!(matches!(hint_at(&p, &instead.to_string()), Hint::Next { .. }));
+    }
+
+    #[test]
+    fn illegal_and_unsolvable() {
+        let p = puzzle("rb gg");
+        assert_eq!(
+            hint_at(&p, "2u"),
+            Hint::Illegal {
+                step: 0,
+                reason: IllegalMove::EarlyChain
+            }
+        );
+
+        let p = puzzle("pp gp");
+        assert_eq!(hint_at(&p, "1u"), Hint::Unsolvable);
+    }
+
+    #[test]
+    fn unreachable_solution() {
+        // 赤 3 つの 1 列目は、12 段目まで埋まった 2 列目の向こうで届かない
+        let mut text = "......\n".to_string();
+        for y in 1..10 {
+            text.push_str(if y % 2 == 0 { ".g....\n" } else { ".b....\n" });
+        }
+        text.push_str("rb....\nrg....\nrb....\nnext: rb\nchain: 1\n");
+        let p: (NaiveField, NazopuyoInfo) = parse_puzzle(&text).unwrap();
+        assert_eq!(hint_at(&p, ""), Hint::Unsolvable);
+    }
+
+    struct LastProgress(Mutex<Option<Progress>>);
+
+    impl ProgressObserver for LastProgress {
+        fn on_progress(&self, progress: &Progress) {
+            *self.0.lock().unwrap() = Some(progress.clone());
+        }
+    }
+
+    /// options で hint を求めて、全部で何ノード訪れたか
+    fn hint_nodes(p: &(NaiveField, NazopuyoInfo), played: &str, options: SolveOptions) -> (Result<Hint, GiveUpReason>, u64) {
+        let last = Arc::new(LastProgress(Mutex::new(None)));
+        let options = options.progress(last.clone(), 1 << 20);
+        let played = parse_placements(played).unwrap();
+        let res = hint(&p.0, &p.1, &played, &options);
+        let nodes = last.0.lock().unwrap().as_ref().unwrap().nodes;
+        (res, nodes)
+    }
+
+    #[test]
+    fn shares_one_budget() {
+        // 二分探索と候補の手でソルバーを何度も呼ぶ
+        let p = puzzle("gg rb");
+        let (res, total) = hint_nodes(&p, "2u", SolveOptions::new());
+        assert!(matches!(res, Ok(Hint::BrokenAt { .. })));
+
+        let (limited, nodes) = hint_nodes(&p, "2u", SolveOptions::new().max_nodes(total));
+        assert_eq!(limited, res);
+        assert_eq!(nodes, total);
+        for n in [1, total / 2, total - 1] {
+            let (limited, nodes) = hint_nodes(&p, "2u", SolveOptions::new().max_nodes(n));
+            assert_eq!(limited, Err(GiveUpReason::NodeLimit));
+            // n + 1 個目は数えたところで打ち切るので、探索したのは n 個まで
+            assert!(nodes <= n + 1, "{} nodes for a budget of {}", nodes, n);
+        }
+    }
+}
@@ -1,3 +1,12 @@
+pub mod cell;
+pub mod rules;
+pub mod score;
+pub mod fever;
+pub mod chain_frames;
+pub mod timing;
+pub mod render;
+pub mod svg;
+pub mod gif;
 pub mod field;
 pub mod naive_field;
 pub mod field1d;
@@ -6,4 +15,20 @@ pub mod nazopuyo_solver;
 pub mod next_puyo;
 pub mod naive_next_puyo;
 pub mod nazopuyo_info;
-pub mod none_recursive_solver;
\ No newline at end of file
+pub mod none_recursive_solver;
+pub mod parallel_solver;
+pub mod solve_options;
+pub mod progress;
+pub mod pruning;
+pub mod beam_search;
+pub mod nazo_url;
+pub mod puzzle_text;
+pub mod placement;
+pub mod generator;
+pub mod difficulty;
+pub mod hint;
+pub mod failure;
+pub mod solver_builder;
+pub mod cli;
+#[cfg(feature = "serde")]
+mod serde_impls;
\ No newline at end of file
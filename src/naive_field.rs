@@ -1,89 +1,68 @@
+use crate::cell::Cell;
 use crate::field;
 
 use super::field::Field;
+use crate::render::{Renderer, Style};
 use std::fmt;
 
+/// W 列 H 段の盤面
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
-pub struct NaiveField {
-    value: [[u8; 13]; 6],
+pub struct NaiveFieldOf<const W: usize = 6, const H: usize = 13> {
+    value: [[u8; H]; W],
 }
 
-impl fmt::Display for NaiveField {
+/// ふつうの 6 列 13 段
+pub type NaiveField = NaiveFieldOf<6, 13>;
+
+impl<const W: usize, const H: usize> fmt::Display for NaiveFieldOf<W, H> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for i in 0..13 {
-            for j in 0..6 {
-                write!(f, "{:2} ", self.get(i, j))?;
-            }
-            writeln!(f)?;
-        }
-        Ok(())
+        f.write_str(&Renderer::new(Style::Ascii).render(self))
     }
 }
 
-impl Field for NaiveField {
-    fn new() -> Self {
-        NaiveField {
-            value: [[0; 13]; 6],
-        }
-    }
-
-    fn from_u8(value: [[u8; 13]; 6]) -> Self {
-        NaiveField { value }
-    }
+impl<const W: usize, const H: usize> Field for NaiveFieldOf<W, H> {
+    const WIDTH: usize = W;
+    const HEIGHT: usize = H;
 
-    fn from_char(value: [[char; 13]; 6]) -> Self {
-        use std::collections::HashMap;
-        let mut map = HashMap::new();
-        map.insert(&'@', &1u8);
-        map.insert(&'r', &2);
-        map.insert(&'b', &3);
-        map.insert(&'g', &4);
-        map.insert(&'y', &5);
-        map.insert(&'p', &6);
-        let mut res = [[0u8; 13]; 6];
-        for j in 0..6 {
-            for i in 0..13 {
-                if map.contains_key(&value[j][i]) {
-                    res[j][i] = **map.get(&value[j][i]).unwrap();
-                } else {
-                    res[j][i] = 0;
-                }
-            }
+    fn new() -> Self {
+        NaiveFieldOf {
+            value: [[0; H]; W],
         }
-
-        Self::from_u8(res)
     }
 
     // fn from(v: [[u32; 13]; 6]) -> Self{
     //             NaiveField { value: v }
     // }
     #[inline]
-    fn set(&mut self, y: usize, x: usize, v: u8) {
-        self.value[x][y] = v
+    fn set(&mut self, y: usize, x: usize, cell: Cell) {
+        self.value[x][y] = cell.code()
     }
 
     #[inline]
-    fn get(&self, y: usize, x: usize) -> u8 {
-        self.value[x][y]
+    fn get(&self, y: usize, x: usize) -> Cell {
+        Cell::decode(self.value[x][y])
     }
 
-    fn fall(&mut self) {
-        for j in 0..6 {
+    fn fall(&mut self) -> usize {
+        let mut distance = 0;
+        for j in 0..W {
             let mut space = 0usize;
-            for i in (0..13).rev() {
-                if self.get(i, j) == 0 {
+            for i in (0..H).rev() {
+                if self.value[j][i] == 0 {
                     space += 1;
                     continue;
                 }
                 if space == 0 {
                     continue;
                 }
+                distance = distance.max(space);
                 let a = self.get(i, j);
                 let b = self.get(i + space, j);
                 self.set(i, j, b);
                 self.set(i + space, j, a);
             }
         }
+        distance
     }
 
     // fn chain(&mut self) -> u32 {
@@ -140,7 +119,7 @@ impl Field for NaiveField {
     //                 if connected.len() >= 4 {
     //                     chained = true;
     //                     for (y, x) in connected {
-    //                         field.set(y, x, 0);
+    //                         field.set(y, x, Cell::Empty);
     //                         for k in 0..4 {
     //                             let ny = y as i32 + dy[k];
     //                             let nx = x as i32 + dx[k];
@@ -153,7 +132,7 @@ impl Field for NaiveField {
     //                             {
     //                                 continue;
     //                             }
-    //                             field.set(ny as usize, nx as usize, 0);
+    //                             field.set(ny as usize, nx as usize, Cell::Empty);
     //                         }
     //                     }
     //                 }
@@ -175,17 +154,9 @@ impl Field for NaiveField {
     fn is_empty(&self) -> bool {
         !self.value.iter().any(|x| x.iter().any(|y| *y != 0))
     }
-
-    #[inline]
-    fn is_alive(&self) -> bool {
-        self.get(1, 2) == 0
-    }
 }
 
-pub fn static_dispatch_chain<T: Field>(field: &mut T) -> u32
-where
-    T: Clone,
-{
+pub fn static_dispatch_chain<T: Field + Clone>(field: &mut T) -> u32 {
     // field.chain()
     field::chain(field)
 }
@@ -254,16 +225,33 @@ mod test {
     #[test]
     fn fall() {
         let mut field = NaiveField::new();
-        field.set(0, 0, 1);
-        field.set(3, 0, 2);
-        field.set(4, 0, 3);
-        field.fall();
+        field.set(0, 0, Cell::Ojama);
+        field.set(3, 0, Cell::RED);
+        field.set(4, 0, Cell::BLUE);
+        assert_eq!(field.fall(), 10);
         let mut req_field = NaiveField::new();
-        req_field.set(10, 0, 1);
-        req_field.set(11, 0, 2);
-        req_field.set(12, 0, 3);
+        req_field.set(10, 0, Cell::Ojama);
+        req_field.set(11, 0, Cell::RED);
+        req_field.set(12, 0, Cell::BLUE);
 
         assert_eq!(field, req_field);
+        assert_eq!(field.fall(), 0);
+    }
+
+    #[test]
+    fn fall_distance() {
+        fn distance<F: Field>() -> usize {
+            let mut field = F::new();
+            field.set(12, 1, Cell::RED);
+            field.set(9, 1, Cell::BLUE);
+            field.set(5, 1, Cell::GREEN);
+            field.set(10, 4, Cell::YELLOW);
+            field.fall()
+        }
+        // 緑の下に空白が 5 個
+        assert_eq!(distance::<NaiveField>(), 5);
+        assert_eq!(distance::<crate::field1d::Field1D>(), 5);
+        assert_eq!(distance::<crate::field_naive_bit::FieldNaiveBit>(), 5);
     }
 
     #[test]
@@ -302,23 +290,99 @@ mod test {
     fn get_top_test() {
         let mut field = NaiveField::new();
         assert_eq!(get_top(&field, 0), None);
-        field.set(12, 0, 1);
+        field.set(12, 0, Cell::Ojama);
         assert_eq!(get_top(&field, 0).unwrap(), 12);
-        field.set(11, 0, 1);
-        field.set(10, 0, 1);
+        field.set(11, 0, Cell::Ojama);
+        field.set(10, 0, Cell::Ojama);
         assert_eq!(get_top(&field, 0).unwrap(), 10);
     }
 
     #[test]
     fn chain_with_ojama() {
         let mut field = NaiveField::new();
-        field.set(12, 0, 1);
-        field.set(12, 1, 2);
-        field.set(12, 2, 2);
-        field.set(11, 0, 1);
-        field.set(11, 1, 2);
-        field.set(11, 2, 2);
+        field.set(12, 0, Cell::Ojama);
+        field.set(12, 1, Cell::RED);
+        field.set(12, 2, Cell::RED);
+        field.set(11, 0, Cell::Ojama);
+        field.set(11, 1, Cell::RED);
+        field.set(11, 2, Cell::RED);
         field::chain(&mut field);
         assert_eq!(field, NaiveField::new());
     }
+
+    #[test]
+    fn wide_and_tall() {
+        fn check<F: Field + Clone>() {
+            let mut field = F::new();
+            let x = F::WIDTH - 1;
+            for y in F::HEIGHT - 3..F::HEIGHT {
+                field.set(y, x, Cell::GREEN);
+            }
+            assert_eq!(get_top(&field, x), Some(F::HEIGHT - 3));
+            field.set(F::HEIGHT - 4, x, Cell::GREEN);
+            field.set(F::HEIGHT - 5, x, Cell::RED);
+            // 右下の隅は 128 マス目より後ろになる盤面もある
+            field.set(F::HEIGHT - 1, x - 1, Cell::Ojama);
+            assert_eq!(field::chain(&mut field), 1);
+            assert_eq!(field.get(F::HEIGHT - 1, x), Cell::RED);
+            assert_eq!(field.get(F::HEIGHT - 1, x - 1), Cell::Empty);
+            assert_eq!(get_top(&field, x), Some(F::HEIGHT - 1));
+        }
+        check::<NaiveFieldOf<8, 16>>();
+        check::<NaiveFieldOf<9, 16>>();
+        check::<crate::field1d::Field1DOf<8, 16>>();
+        check::<crate::field1d::Field1DOf<9, 16>>();
+        check::<crate::field_naive_bit::FieldNaiveBitOf<8, 16>>();
+    }
+
+    #[test]
+    fn chain_with_hard() {
+        // 固ぷよは 1 回巻き込むとおじゃまになる。2 グループの隣でも同じ連鎖なら 1 回
+        let mut field = NaiveField::new();
+        for x in 0..2 {
+            field.set(12, x, Cell::RED);
+            field.set(11, x, Cell::RED);
+        }
+        for x in 3..5 {
+            field.set(12, x, Cell::BLUE);
+            field.set(11, x, Cell::BLUE);
+        }
+        field.set(12, 2, Cell::Hard);
+        assert_eq!(field::chain(&mut field), 1);
+        let mut expected = NaiveField::new();
+        expected.set(12, 2, Cell::Ojama);
+        assert_eq!(field, expected);
+    }
+
+    #[test]
+    fn chain_with_rules() {
+        use crate::rules::{OjamaClear, Rules};
+        // 見えない段が 2 段なら 1 段目の赤はつながらない
+        let mut field = NaiveField::new();
+        for y in 5..13 {
+            field.set(y, 0, if y % 2 == 0 { Cell::BLUE } else { Cell::GREEN });
+        }
+        for y in 1..5 {
+            field.set(y, 0, Cell::RED);
+        }
+        assert!(field::has_chain(&field));
+        assert!(!field::has_chain_with(&field, &Rules::TSU.hidden_rows(2)));
+        assert_eq!(field::chain_with(&mut field.clone(), &Rules::TSU.hidden_rows(2)), 0);
+        assert!(!field::is_alive_with(&field, &Rules::TSU.death_cell(1, 0)));
+        assert!(field::is_alive_with(&field, &Rules::TSU));
+
+        // おじゃまが消えないルール
+        let mut field = NaiveField::new();
+        for x in 0..4 {
+            field.set(12, x, Cell::RED);
+        }
+        field.set(11, 0, Cell::Ojama);
+        let mut never = field.clone();
+        assert_eq!(field::chain(&mut field), 1);
+        assert!(field.is_empty());
+        assert_eq!(field::chain_with(&mut never, &Rules::TSU.ojama_clear(OjamaClear::Never)), 1);
+        let mut expected = NaiveField::new();
+        expected.set(12, 0, Cell::Ojama);
+        assert_eq!(never, expected);
+    }
 }
@@ -1,21 +1,147 @@
+use std::fmt;
+
+use crate::cell::{Cell, Color};
+
+/// ネクストに入る組の数の上限
+pub const MAX_PAIRS: usize = 10;
+
+/// ネクストを作れない理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NextError {
+    /// MAX_PAIRS 組より多い
+    TooManyPairs(usize),
+    /// pair 組目 (0 始まり) に色ぷよ (2-6) でない番号がある
+    BadColor { pair: usize, code: u8 },
+}
+
+impl fmt::Display for NextError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NextError::TooManyPairs(n) => write!(f, "{} pairs, expected at most {}", n, MAX_PAIRS),
+            NextError::BadColor { pair, code } => {
+                write!(f, "pair {} has color code {}, expected 2-6", pair + 1, code)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NextError {}
+
+/// ネクスト。中身は作るときに検査するので pair で decode しても panic しない
 #[derive(Debug, Clone)]
 pub struct NaiveNextPuyo {
-    pub value: [[u8; 2]; 10],
-    pub len: usize,
+    value: [[u8; 2]; MAX_PAIRS],
+    len: usize,
+}
+
+impl Default for NaiveNextPuyo {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl NaiveNextPuyo {
     pub fn new() -> Self {
         NaiveNextPuyo {
-            value: [[0u8; 2]; 10],
+            value: [[0u8; 2]; MAX_PAIRS],
             len: 0,
         }
     }
 
-    pub fn from_vec(v: Vec<[u8; 2]>) -> Self {
-        NaiveNextPuyo {
-            len: v.len(),
-            value: v.try_into().unwrap(),
+    /// 各組を色の番号 (2-6) で。色ぷよでない番号や多すぎる組はエラー
+    pub fn from_vec(v: Vec<[u8; 2]>) -> Result<Self, NextError> {
+        if v.len() > MAX_PAIRS {
+            return Err(NextError::TooManyPairs(v.len()));
+        }
+        for (pair, p) in v.iter().enumerate() {
+            if let Some(&code) = p.iter().find(|&&c| Color::from_code(c).is_none()) {
+                return Err(NextError::BadColor { pair, code });
+            }
+        }
+        let mut res = Self::new();
+        res.len = v.len();
+        res.value[..v.len()].copy_from_slice(&v);
+        Ok(res)
+    }
+
+    /// 色で渡すなら番号の検査はいらない
+    /// MAX_PAIRS 組より多いと panic する
+    pub fn from_colors(v: Vec<[Color; 2]>) -> Self {
+        assert!(v.len() <= MAX_PAIRS, "{} pairs, expected at most {}", v.len(), MAX_PAIRS);
+        let mut res = Self::new();
+        res.len = v.len();
+        for (dst, src) in res.value.iter_mut().zip(v) {
+            *dst = src.map(Color::code);
+        }
+        res
+    }
+
+    /// 組の数
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// 全部の組をマスの番号で
+    pub fn pairs(&self) -> &[[u8; 2]] {
+        &self.value[..self.len]
+    }
+
+    /// i 組目をマスとして ([0] が軸ぷよ)
+    #[inline]
+    pub fn pair(&self, i: usize) -> [Cell; 2] {
+        self.value[i].map(Cell::decode)
+    }
+
+    /// 先頭の n 組を除いたもの
+    pub fn skip(&self, n: usize) -> Self {
+        let mut res = Self::new();
+        let rest = &self.pairs()[n.min(self.len)..];
+        res.len = rest.len();
+        res.value[..rest.len()].copy_from_slice(rest);
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::field::Field;
+    use crate::naive_field::NaiveField;
+    use crate::nazopuyo_info::NazopuyoInfo;
+    use crate::placement::{parse_placements, verify, Verdict};
+
+    #[test]
+    fn rejects_bad_codes() {
+        assert_eq!(NaiveNextPuyo::from_vec(vec![[9, 2]]).unwrap_err(), NextError::BadColor { pair: 0, code: 9 });
+        // 空白・おじゃま・固ぷよはネクストに入らない
+        for code in [0, 1, 7] {
+            assert_eq!(
+                NaiveNextPuyo::from_vec(vec![[2, 3], [3, code]]).unwrap_err(),
+                NextError::BadColor { pair: 1, code }
+            );
         }
+        assert_eq!(NaiveNextPuyo::from_vec(vec![[2, 3]; 11]).unwrap_err(), NextError::TooManyPairs(11));
+        assert_eq!(NaiveNextPuyo::from_vec(vec![[2, 3]; 10]).unwrap().len(), 10);
+
+        let next = NaiveNextPuyo::from_vec(vec![[2, 3], [4, 5]]).unwrap();
+        assert_eq!(next.pairs(), &[[2, 3], [4, 5]]);
+        assert_eq!(next.pair(1), [Cell::GREEN, Cell::YELLOW]);
+        assert_eq!(next.skip(1).pairs(), &[[4, 5]]);
+        assert!(next.skip(3).is_empty());
+
+        let info = NazopuyoInfo::new(1, next);
+        let played = parse_placements("1u 1u").unwrap();
+        assert_eq!(verify(&NaiveField::new(), &info, &played), Verdict::WrongChain { expected: 1, actual: 0 });
+    }
+
+    #[test]
+    #[should_panic(expected = "11 pairs, expected at most 10")]
+    fn too_many_colors() {
+        NaiveNextPuyo::from_colors(vec![[Color::Red, Color::Blue]; 11]);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,294 @@
+//! なぞぷよエディタ (pn.html) の URL との相互変換
+//!
+//! `pn.html?<盤面>_<ネクスト>__<クリア条件>` の形をしている
+//! - 盤面: 13 段目から下へ行優先に 2 マスずつ 1 文字 (左 * 8 + 右)、先頭の 0 は省略される
+//! - ネクスト: 1 組 2 文字 (軸, 子)。下位 3 bit が色で、上位 bit (エディタが記録した置き場所) は読み飛ばす
+//! - クリア条件: 種類, 色, 数 の 3 文字。ここで扱えるのは `u` (n 連鎖する) だけ
+//!
+//! エディタの盤面は 6 列 13 段なので、それ以外の大きさの盤面は扱えない
+
+use std::fmt;
+
+use crate::cell::Cell;
+use crate::field::Field;
+use crate::naive_next_puyo::NaiveNextPuyo;
+use crate::nazopuyo_info::NazopuyoInfo;
+
+const CHARS: &[u8; 64] = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ.-";
+
+/// クリア条件「n 連鎖する」の種類番号 ('u')
+const QUEST_CHAIN: u8 = 30;
+
+/// 盤面のマス数 (6 列 * 13 段)
+const CELLS: usize = 78;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UrlError {
+    /// 符号に使われない文字
+    InvalidChar(char),
+    /// 盤面が 78 マスより長い
+    FieldTooLong(usize),
+    /// 読み込む先の盤面が 6 列 13 段でない
+    UnsupportedSize { width: usize, height: usize },
+    /// ネクストの色が不正
+    InvalidNext(u8),
+    /// ネクストの文字数が奇数
+    OddNext,
+    /// ネクストが 10 組より多い
+    TooManyNext(usize),
+    /// クリア条件が無い、または短い
+    MissingQuest,
+    /// n 連鎖する 以外のクリア条件
+    UnsupportedQuest(char),
+    /// 書き出す連鎖数が 1 文字 (64 未満) に収まらない
+    ChainTooLong(u32),
+}
+
+impl fmt::Display for UrlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UrlError::InvalidChar(c) => write!(f, "invalid character {:?}", c),
+            UrlError::FieldTooLong(n) => write!(f, "field has {} cells (max {})", n, CELLS),
+            UrlError::UnsupportedSize { width, height } => {
+                write!(f, "pn.html fields are 6x13, not {}x{}", width, height)
+            }
+            UrlError::InvalidNext(c) => write!(f, "invalid next color code {}", c),
+            UrlError::OddNext => write!(f, "next queue has an odd number of puyos"),
+            UrlError::TooManyNext(n) => write!(f, "next queue has {} pairs (max 10)", n),
+            UrlError::MissingQuest => write!(f, "missing quest condition"),
+            UrlError::UnsupportedQuest(c) => write!(f, "unsupported quest type {:?}", c),
+            UrlError::ChainTooLong(n) => write!(f, "chain count {} does not fit pn.html (max 63)", n),
+        }
+    }
+}
+
+impl std::error::Error for UrlError {}
+
+fn decode_char(c: char) -> Result<u8, UrlError> {
+    CHARS
+        .iter()
+        .position(|&x| x as char == c)
+        .map(|i| i as u8)
+        .ok_or(UrlError::InvalidChar(c))
+}
+
+fn encode_char(v: u8) -> char {
+    CHARS[v as usize] as char
+}
+
+/// エディタの色番号 -> マス
+/// エディタ: 1 赤, 2 緑, 3 青, 4 黄, 5 紫, 6 おじゃま, 7 固ぷよ
+fn from_editor_color(c: u8) -> Cell {
+    match c & 7 {
+        1 => Cell::RED,
+        2 => Cell::GREEN,
+        3 => Cell::BLUE,
+        4 => Cell::YELLOW,
+        5 => Cell::PURPLE,
+        6 => Cell::Ojama,
+        7 => Cell::Hard,
+        _ => Cell::Empty,
+    }
+}
+
+fn to_editor_color(c: Cell) -> u8 {
+    match c {
+        Cell::Empty => 0,
+        Cell::RED => 1,
+        Cell::GREEN => 2,
+        Cell::BLUE => 3,
+        Cell::YELLOW => 4,
+        Cell::PURPLE => 5,
+        Cell::Ojama => 6,
+        Cell::Hard => 7,
+    }
+}
+
+/// URL (またはその ? 以降) を読む
+pub fn parse_url<F: Field>(url: &str) -> Result<(F, NazopuyoInfo), UrlError> {
+    if F::WIDTH != 6 || F::HEIGHT != 13 {
+        return Err(UrlError::UnsupportedSize {
+            width: F::WIDTH,
+            height: F::HEIGHT,
+        });
+    }
+    let query = match url.find('?') {
+        Some(i) => &url[i + 1..],
+        None => url,
+    };
+    let (body, quest) = match query.find("__") {
+        Some(i) => (&query[..i], &query[i + 2..]),
+        None => return Err(UrlError::MissingQuest),
+    };
+    let (field_part, next_part) = match body.find('_') {
+        Some(i) => (&body[..i], &body[i + 1..]),
+        None => (body, ""),
+    };
+
+    let field_len = field_part.chars().count() * 2;
+    if field_len > CELLS {
+        return Err(UrlError::FieldTooLong(field_len));
+    }
+    let mut cells = vec![0u8; CELLS - field_len];
+    for c in field_part.chars() {
+        let v = decode_char(c)?;
+        cells.push(v >> 3);
+        cells.push(v & 7);
+    }
+    let mut field = F::new();
+    for (k, &c) in cells.iter().enumerate() {
+        field.set(k / 6, k % 6, from_editor_color(c));
+    }
+
+    let colors = next_part
+        .chars()
+        .map(|c| {
+            let v = decode_char(c)? & 7;
+            match from_editor_color(v).color() {
+                Some(color) => Ok(color),
+                None => Err(UrlError::InvalidNext(v)),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if colors.len() % 2 != 0 {
+        return Err(UrlError::OddNext);
+    }
+    if colors.len() / 2 > 10 {
+        return Err(UrlError::TooManyNext(colors.len() / 2));
+    }
+    let next = NaiveNextPuyo::from_colors(colors.chunks(2).map(|p| [p[0], p[1]]).collect());
+
+    let quest: Vec<char> = quest.chars().collect();
+    if quest.len() < 3 {
+        return Err(UrlError::MissingQuest);
+    }
+    if decode_char(quest[0])? != QUEST_CHAIN {
+        return Err(UrlError::UnsupportedQuest(quest[0]));
+    }
+    let chain = decode_char(quest[2])? as u32;
+
+    Ok((field, NazopuyoInfo::new(chain, next)))
+}
+
+/// URL の ? 以降を作る。盤面が 6 列 13 段でないか、連鎖数が 1 文字 (64 未満) に収まらなければエラー
+pub fn to_query<F: Field>(field: &F, info: &NazopuyoInfo) -> Result<String, UrlError> {
+    if F::WIDTH != 6 || F::HEIGHT != 13 {
+        return Err(UrlError::UnsupportedSize {
+            width: F::WIDTH,
+            height: F::HEIGHT,
+        });
+    }
+    if info.chain >= 64 {
+        return Err(UrlError::ChainTooLong(info.chain));
+    }
+    let mut res = String::new();
+    for k in (0..CELLS).step_by(2) {
+        let left = to_editor_color(field.get(k / 6, k % 6));
+        let right = to_editor_color(field.get(k / 6, k % 6 + 1));
+        let c = left * 8 + right;
+        if res.is_empty() && c == 0 {
+            continue;
+        }
+        res.push(encode_char(c));
+    }
+
+    res.push('_');
+    for i in 0..info.next.len() {
+        for cell in info.next.pair(i) {
+            res.push(encode_char(to_editor_color(cell)));
+        }
+    }
+
+    res.push_str("__");
+    res.push(encode_char(QUEST_CHAIN));
+    res.push('0');
+    res.push(encode_char(info.chain as u8));
+    Ok(res)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::field_naive_bit::FieldNaiveBit;
+    use crate::naive_field::{NaiveField, NaiveFieldOf};
+
+    #[test]
+    fn parse() {
+        // 一番下の段: 赤赤赤 緑緑 青, その上: 赤だけ
+        let url = "http://ips.karou.jp/simu/pn.html?8009aj_1214__u01";
+        let (field, info) = parse_url::<NaiveField>(url).unwrap();
+        assert_eq!(field.get(11, 0), Cell::RED);
+        assert_eq!(field.get(11, 1), Cell::Empty);
+        assert_eq!(field.get(12, 0), Cell::RED);
+        assert_eq!(field.get(12, 1), Cell::RED);
+        assert_eq!(field.get(12, 2), Cell::RED);
+        assert_eq!(field.get(12, 3), Cell::GREEN);
+        assert_eq!(field.get(12, 4), Cell::GREEN);
+        assert_eq!(field.get(12, 5), Cell::BLUE);
+        assert_eq!(info.chain, 1);
+        assert_eq!(info.next.len(), 2);
+        assert_eq!(info.next.pairs()[0], [2, 4]);
+        assert_eq!(info.next.pairs()[1], [2, 5]);
+    }
+
+    #[test]
+    fn round_trip() {
+        let value = [
+            [' ', ' ', ' ', ' ', ' ', ' ', ' ', 'g', 'g', 'g', 'b', 'b', 'g'],
+            [' ', ' ', ' ', ' ', ' ', ' ', ' ', 'b', 'b', 'r', 'r', 'b', 'g'],
+            [' ', ' ', ' ', ' ', ' ', ' ', ' ', 'b', 'y', 'r', 'y', 'y', 'g'],
+            [' ', ' ', ' ', ' ', ' ', ' ', ' ', 'g', 'y', 'y', 'r', 'y', 'b'],
+            [' ', ' ', ' ', ' ', ' ', ' ', ' ', 'g', 'b', 'r', 'r', 'b', 'b'],
+            ['@', ' ', ' ', ' ', ' ', ' ', ' ', 'g', 'b', 'b', 'g', 'g', 'p'],
+        ];
+        let field = FieldNaiveBit::from_char(value);
+        let next = NaiveNextPuyo::from_vec(vec![[2, 3], [2, 4], [5, 5], [3, 6]]).unwrap();
+        let info = NazopuyoInfo::new(12, next);
+
+        let query = to_query(&field, &info).unwrap();
+        let (field2, info2) = parse_url::<FieldNaiveBit>(&query).unwrap();
+        assert_eq!(field, field2);
+        assert_eq!(info.chain, info2.chain);
+        assert_eq!(info.next.len(), info2.next.len());
+        assert_eq!(info.next.pairs(), info2.next.pairs());
+        assert_eq!(to_query(&field2, &info2), Ok(query));
+    }
+
+    #[test]
+    fn write_errors() {
+        let next = NaiveNextPuyo::from_vec(vec![[2, 3]]).unwrap();
+        let info = NazopuyoInfo::new(64, next.clone());
+        assert_eq!(to_query(&NaiveField::new(), &info), Err(UrlError::ChainTooLong(64)));
+        assert_eq!(UrlError::ChainTooLong(64).to_string(), "chain count 64 does not fit pn.html (max 63)");
+        let info = NazopuyoInfo::new(63, next);
+        assert!(to_query(&NaiveField::new(), &info).unwrap().ends_with("__u0-"));
+        assert_eq!(
+            to_query(&NaiveFieldOf::<8, 13>::new(), &info),
+            Err(UrlError::UnsupportedSize { width: 8, height: 13 })
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            parse_url::<NaiveField>("pn.html?8_12__v01").unwrap_err(),
+            UrlError::UnsupportedQuest('v')
+        );
+        assert_eq!(
+            parse_url::<NaiveField>("pn.html?8_12").unwrap_err(),
+            UrlError::MissingQuest
+        );
+        assert_eq!(
+            parse_url::<NaiveField>("pn.html?8_1__u01").unwrap_err(),
+            UrlError::OddNext
+        );
+        assert_eq!(
+            parse_url::<NaiveField>("pn.html?8!_12__u01").unwrap_err(),
+            UrlError::InvalidChar('!')
+        );
+        assert_eq!(
+            parse_url::<NaiveFieldOf<8, 13>>("pn.html?8_12__u01").unwrap_err(),
+            UrlError::UnsupportedSize { width: 8, height: 13 }
+        );
+    }
+}
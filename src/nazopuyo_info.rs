@@ -1,7 +1,25 @@
 use crate::naive_next_puyo::NaiveNextPuyo;
+use crate::rules::Rules;
 
 #[derive(Debug, Clone)]
 pub struct NazopuyoInfo {
     pub chain: u32,
     pub next: NaiveNextPuyo,
-}
\ No newline at end of file
+    pub rules: Rules,
+}
+
+impl NazopuyoInfo {
+    /// ぷよぷよ通のルールで chain 連鎖する問題
+    pub fn new(chain: u32, next: NaiveNextPuyo) -> Self {
+        Self {
+            chain,
+            next,
+            rules: Rules::default(),
+        }
+    }
+
+    pub fn rules(mut self, rules: Rules) -> Self {
+        self.rules = rules;
+        self
+    }
+}
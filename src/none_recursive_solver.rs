@@ -1,22 +1,18 @@
-use std::borrow::BorrowMut;
 use std::collections::HashSet;
 use std::hash::Hash;
-use std::time::Duration;
 
-use rand::Rng;
-
-
-use crate::field::{self, get_top, Field};
-use crate::field1d::Field1D;
+use crate::failure::{explain, FailureReport};
+use crate::field::{self, Field};
 use crate::field_naive_bit::FieldNaiveBit;
 use crate::naive_field::NaiveField;
 // use crate::naive_next_puyo::NaiveNextPuyo;
 use crate::nazopuyo_info::NazopuyoInfo;
 // use crate::next_puyo::NexuPuyo;
-
-use std::sync::mpsc;
-use std::sync::mpsc::{Receiver, Sender};
-use std::thread::{self, ThreadId};
+use crate::parallel_solver::{default_workers, expand, solve_parallel};
+use crate::progress::count_first_ply;
+use crate::pruning::{any_prune, Pruner};
+use crate::puzzle_text::parse_puzzle;
+use crate::solve_options::{Budget, SolveOptions, SolveResult};
 
 // use super::field::Field;
 
@@ -28,442 +24,250 @@ where
     field: F, //Box<dyn Field>,
     info: NazopuyoInfo,
     hash: HashSet<F>,
+    pruners: Vec<Box<dyn Pruner<F>>>,
+    /// solve, solve_multi で使う打ち切り条件
+    options: SolveOptions,
+    /// field が何手置いたあとの盤面か
+    depth: u32,
 }
 
 impl<F> NoneRecursiveSolver<F>
 where
     F: Field + Clone + PartialEq + Eq + Hash + std::fmt::Display + Send + 'static,
 {
-    fn new(field: F, info: NazopuyoInfo) -> Self {
+    /// 外からは SolverBuilder で作る
+    pub(crate) fn new(field: F, info: NazopuyoInfo) -> Self {
+        Self::from_position(field, info, 0)
+    }
+    /// depth 手置いたあとの盤面から探索する (ネクストは info.next.pair(depth) から使う)
+    pub(crate) fn from_position(field: F, info: NazopuyoInfo, depth: u32) -> Self {
+        assert!(depth as usize <= info.next.len(), "depth is past the end of the next queue");
         Self {
             field,
             info,
             hash: HashSet::new(),
+            pruners: vec![],
+            options: SolveOptions::default(),
+            depth,
         }
     }
+    /// 枝刈りを追加する
+    pub fn add_pruner<P: Pruner<F> + 'static>(&mut self, pruner: P) {
+        self.pruners.push(Box::new(pruner));
+    }
+    pub(crate) fn add_boxed_pruner(&mut self, pruner: Box<dyn Pruner<F>>) {
+        self.pruners.push(pruner);
+    }
+    /// solve, solve_multi で使う打ち切り条件を変える
+    pub fn set_options(&mut self, options: SolveOptions) {
+        self.options = options;
+    }
     pub fn solve(&mut self) -> Option<F> {
-        match self.non_recursive_dfs(self.field.clone(), self.info.clone(), 0) {
-            Some(field) => Some(field),
-            None => None,
-        }
+        self.solve_with(&self.options.clone()).solution()
+    }
+    /// 打ち切り条件付きで探索する
+    pub fn solve_with(&mut self, options: &SolveOptions) -> SolveResult<F> {
+        let budget = Budget::new(options, self.info.next.len());
+        budget.set_first_ply_total(count_first_ply(&self.field, &self.info, self.depth));
+        let res = self.non_recursive_dfs(self.field.clone(), self.info.clone(), self.depth, &budget);
+        budget.finish();
+        res
     }
-    fn non_recursive_dfs(&mut self, mut original_field: F, info: NazopuyoInfo, depth: u32) -> Option<F> {
-        let mut stack = vec![];
-        stack.push((0u32, original_field.clone()));
-        let indicies = [0, 1, 5, 3, 2, 4]; // 反転
+    fn non_recursive_dfs(
+        &mut self,
+        original_field: F,
+        info: NazopuyoInfo,
+        depth: u32,
+        budget: &Budget,
+    ) -> SolveResult<F> {
+        // (何手置いたか, 盤面, どの初手の下か (根は None))
+        let mut stack = vec![(depth, original_field, None)];
+        let mut children = vec![];
 
-        while !stack.is_empty() {
-            let (depth, mut field) = stack.pop().unwrap();
-            if depth == info.next.len as u32 {
+        while let Some((depth, mut field, branch)) = stack.pop() {
+            if let Some(reason) = budget.visit(depth) {
+                return SolveResult::GaveUp(reason);
+            }
+            if depth == info.next.len() as u32 {
                 let f2 = field.clone();
-                if field::chain(&mut field) == info.chain {
-                    return Some(f2);
-                } else {
-                    continue;
+                if field::chain_with(&mut field, &info.rules) == info.chain {
+                    return SolveResult::Solved(f2);
                 }
+                budget.expanded(branch, 0);
+                continue;
             }
-
-            for j in indicies {
-                for index in 0..2 {
-                    let y1 = match get_top(&field, j) {
-                        Some(y) => match y {
-                            0 => continue,
-                            _ => y - 1,
-                        },
-                        None => 12,
-                    };
-                    field.set(y1, j, info.next.value[depth as usize][index]);
-                    for dx in 0..2 {
-                        if j + dx >= 6 {
-                            continue;
-                        }
-                        let y2 = match get_top(&field, j + dx) {
-                            Some(y) => match y {
-                                0 => {
-                                    // field.set(y1, j, 0);
-                                    continue;
-                                }
-                                _ => y - 1,
-                            },
-                            None => 12,
-                        };
-                        field.set(y2, j + dx, info.next.value[depth as usize][index ^ 1]);
-                        if !self.hash.contains(&field)
-                            && (depth + 1 == info.next.len as u32
-                                || (field.is_alive() && !field::has_chain(&field)))
-                        {
-                            self.hash.insert(field.clone());
-                            stack.push((depth + 1, field.clone()));
-                        }
-                        field.set(y2, j + dx, 0);
-                    }
-                    field.set(y1, j, 0);
-                }
+            if any_prune(&self.pruners, &field, &info, depth) {
+                budget.expanded(branch, 0);
+                continue;
             }
-        }
-        None
-    }
 
-    pub fn solve_multi(&mut self) -> Option<F> {
-        let (tx, rx) = mpsc::channel();
-
-        let mut fields = vec![];
-        let indicies = [2, 4, 3, 5, 1, 0];
-
-        for j in indicies {
-            for index in 0..2 {
-                let y1 = match get_top(&self.field, j) {
-                    Some(y) => match y {
-                        0 => continue,
-                        _ => y - 1,
-                    },
-                    None => 12,
-                };
-                self.field.set(y1, j, self.info.next.value[0][index]);
-                for dx in 0..2 {
-                    if j + dx >= 6 {
-                        continue;
-                    }
-                    let y2 = match get_top(&self.field, j + dx) {
-                        Some(y) => match y {
-                            0 => {
-                                // field.set(y1, j, 0);
-                                continue;
-                            }
-                            _ => y - 1,
-                        },
-                        None => 12,
-                    };
-                    self.field
-                    .set(y2, j + dx, self.info.next.value[0][index ^ 1]);
-                    if !self.hash.contains(&self.field)
-                        && self.field.is_alive()
-                        && !field::has_chain(&self.field)
-                    {
-                        self.hash.insert(self.field.clone());
-                        fields.push(self.field.clone());
-                    }
-                    self.field.set(y2, j + dx, 0);
+            // 積んだ逆順に取り出すので、Solver::dfs と同じ順に調べるよう逆に積む
+            expand(&mut field, &info, depth, &mut children);
+            let mut pushed = 0;
+            for child in children.drain(..).rev() {
+                let hit = self.hash.contains(&child);
+                budget.lookup(hit);
+                if !hit {
+                    self.hash.insert(child.clone());
+                    let branch = branch.unwrap_or_else(|| budget.open_first_ply());
+                    stack.push((depth + 1, child, Some(branch)));
+                    pushed += 1;
                 }
-                self.field.set(y1, j, 0);
             }
+            budget.expanded(branch, pushed);
         }
-
-        println!("{} patterns!", fields.len());
-
-        // for f in fields.iter() {
-        //     println!("{}", f);
-        // }
-        let mut handles = Vec::new();
-        let info2 = self.info.clone();
-        for f in fields.into_iter() {
-            let tx = tx.clone();
-            let mut f2 = f.clone();
-            let info3 = info2.clone();
-            let mut hash2 = self.hash.clone();
-            let handle = thread::spawn(move || {
-                let res = parallel_dfs(&mut f2, info3, 1, hash2.borrow_mut());
-                if res.is_some() {
-                    println!("found!");
-                    tx.send(res).ok();
-                } else {
-                    println!("No result for {:?}", thread::current().id());
-                }
-            });
-            handles.push(handle);
-            //thread::sleep(Duration::from_millis(1000));
-        }
-
-        // for handle in handles {
-        //     handle.join().unwrap();
-        // }
-        drop(tx);
-
-        rx.recv().ok().and_then(|opt| opt)
+        SolveResult::NoSolution
     }
-}
 
-fn parallel_dfs<F>(
-    field: &mut F,
-    info: NazopuyoInfo,
-    depth: u32,
-    hash: &mut HashSet<F>,
-) -> Option<F>
-where
-    F: Field + Clone + PartialEq + Eq + Hash + std::fmt::Display + Send,
-{
-    if depth == info.next.len as u32 {
-        let f2 = &mut field.clone();
-        let f3 = field.clone();
-        if field::chain(f2) == info.chain {
-            return Some(f3);
-        } else {
-            return None;
-        }
+    /// 解が無かった理由を調べる
+    pub fn explain(&self, options: &SolveOptions) -> FailureReport<F> {
+        explain(&self.field, &self.info, self.depth, options, &self.pruners)
     }
 
-    let indicies = [2, 4, 3, 5, 1, 0];
-
-    for j in indicies {
-        for index in 0..2 {
-            let y1 = match get_top(field, j) {
-                Some(y) => match y {
-                    0 => continue,
-                    _ => y - 1,
-                },
-                None => 12,
-            };
-            field.set(y1, j, info.next.value[depth as usize][index]);
-            for dx in 0..2 {
-                if j + dx >= 6 {
-                    continue;
-                }
-                let y2 = match get_top(field, j + dx) {
-                    Some(y) => match y {
-                        0 => {
-                            // field.set(y1, j, 0);
-                            continue;
-                        }
-                        _ => y - 1,
-                    },
-                    None => 12,
-                };
-                field.set(y2, j + dx, info.next.value[depth as usize][index ^ 1]);
+    /// 全コアで並列に探索する
+    pub fn solve_multi(&mut self) -> Option<F> {
+        self.solve_multi_with(&self.options.clone()).solution()
+    }
 
-                if !hash.contains(&field)
-                    && (depth + 1 == info.next.len as u32
-                        || (field.is_alive() && !field::has_chain(field)))
-                {
-                    hash.insert(field.clone());
-                    {
-                        let res = parallel_dfs(field, info.clone(), depth + 1, hash.borrow_mut());
-                        if res.is_some() {
-                            return res;
-                        }
-                    }
-                }
-                field.set(y2, j + dx, 0);
-            }
-            field.set(y1, j, 0);
-        }
+    pub fn solve_multi_with(&mut self, options: &SolveOptions) -> SolveResult<F> {
+        // 並列探索は 0 手目から始めるので、置いた分のネクストを除いて渡す
+        let info = NazopuyoInfo {
+            next: self.info.next.skip(self.depth as usize),
+            ..self.info.clone()
+        };
+        solve_parallel(
+            self.field.clone(),
+            info,
+            default_workers(),
+            options,
+            &self.pruners,
+        )
     }
-    None
 }
 
 #[allow(unused)]
 pub fn chain_5depth() {
-    let value = [
-        [0u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 5, 5],
-        [0u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 5, 2],
-        [0u8, 0, 0, 0, 5, 5, 3, 3, 4, 4, 5, 2, 2],
-        [0u8, 0, 0, 0, 0, 5, 3, 2, 4, 5, 5, 3, 3],
-        [0u8, 0, 0, 0, 0, 0, 0, 2, 2, 3, 3, 5, 3],
-        [0u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 5, 5],
-    ];
-    let field = NaiveField::from_u8(value);
+    let (field, info) =
+        parse_puzzle::<NaiveField>(include_str!("../puzzles/chain_5depth.txt")).unwrap();
     println!("{}", field);
-    let mut next = crate::naive_next_puyo::NaiveNextPuyo::new();
-    next.len = 5;
-    next.value[0][0] = 3;
-    next.value[0][1] = 5;
-    next.value[1][0] = 2;
-    next.value[1][1] = 3;
-    next.value[2][0] = 4;
-    next.value[2][1] = 5;
-    next.value[3][0] = 2;
-    next.value[3][1] = 5;
-    next.value[4][0] = 3;
-    next.value[4][1] = 5;
-    let info = NazopuyoInfo { chain: 10, next };
     let mut solver = NoneRecursiveSolver::new(field, info);
-    let res = solver.solve().unwrap();
-    println!("{}", res);
+    match solver.solve() {
+        Some(res) => println!("{}", res),
+        None => println!("{}", solver.explain(&SolveOptions::default())),
+    }
 }
 
 #[allow(unused)]
 pub fn chain_6depth() {
-    let value = [
-        [
-            ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ',
-        ],
-        [
-            ' ', ' ', ' ', ' ', 'p', 'p', 'r', 'r', 'b', 'g', 'g', 'r', 'r',
-        ],
-        [
-            ' ', ' ', ' ', ' ', 'p', 'b', 'b', 'r', 'b', 'b', 'g', 'y', 'r',
-        ],
-        [
-            ' ', ' ', ' ', ' ', 'r', 'b', 'g', 'g', 'y', 'r', 'y', 'y', 'b',
-        ],
-        [
-            ' ', ' ', ' ', ' ', 'r', 'r', 'g', 'y', 'y', 'r', 'r', 'b', 'b',
-        ],
-        [
-            ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ',
-        ],
-    ];
-    let field = FieldNaiveBit::from_char(value);
+    let (field, info) =
+        parse_puzzle::<FieldNaiveBit>(include_str!("../puzzles/chain_6depth.txt")).unwrap();
     println!("{}", field);
-    let mut next = crate::naive_next_puyo::NaiveNextPuyo::new();
-    next.len = 6;
-    next.value[0][0] = 2;
-    next.value[0][1] = 3;
-    next.value[1][0] = 2;
-    next.value[1][1] = 4;
-    next.value[2][0] = 5;
-    next.value[2][1] = 3;
-    next.value[3][0] = 6;
-    next.value[3][1] = 4;
-    next.value[4][0] = 2;
-    next.value[4][1] = 5;
-    next.value[5][0] = 2;
-    next.value[5][1] = 3;
-    let info = NazopuyoInfo { chain: 12, next };
     let mut solver = NoneRecursiveSolver::new(field, info);
-    let res = solver.solve().unwrap();
-    println!("{}", res);
+    match solver.solve() {
+        Some(res) => println!("{}", res),
+        None => println!("{}", solver.explain(&SolveOptions::default())),
+    }
 }
 
 #[allow(unused)]
 pub fn multi_test() {
-    let value = [
-        [
-            ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ',
-        ],
-        [
-            ' ', ' ', ' ', ' ', 'p', 'p', 'r', 'r', 'b', 'g', 'g', 'r', 'r',
-        ],
-        [
-            ' ', ' ', ' ', ' ', 'p', 'b', 'b', 'r', 'b', 'b', 'g', 'y', 'r',
-        ],
-        [
-            ' ', ' ', ' ', ' ', 'r', 'b', 'g', 'g', 'y', 'r', 'y', 'y', 'b',
-        ],
-        [
-            ' ', ' ', ' ', ' ', 'r', 'r', 'g', 'y', 'y', 'r', 'r', 'b', 'b',
-        ],
-        [
-            ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ',
-        ],
-    ];
-    let field = FieldNaiveBit::from_char(value);
+    let (field, info) =
+        parse_puzzle::<FieldNaiveBit>(include_str!("../puzzles/chain_6depth.txt")).unwrap();
     println!("{}", field);
-    let mut next = crate::naive_next_puyo::NaiveNextPuyo::new();
-    next.len = 6;
-    next.value[0][0] = 2;
-    next.value[0][1] = 3;
-    next.value[1][0] = 2;
-    next.value[1][1] = 4;
-    next.value[2][0] = 5;
-    next.value[2][1] = 3;
-    next.value[3][0] = 6;
-    next.value[3][1] = 4;
-    next.value[4][0] = 2;
-    next.value[4][1] = 5;
-    next.value[5][0] = 2;
-    next.value[5][1] = 3;
-    let info = NazopuyoInfo { chain: 12, next };
     let mut solver = NoneRecursiveSolver::new(field, info);
-    let res = solver.solve_multi();
-    println!("{}", res.unwrap());
+    match solver.solve_multi() {
+        Some(res) => println!("{}", res),
+        None => println!("{}", solver.explain(&SolveOptions::default())),
+    }
 }
 
 #[allow(unused)]
 pub fn multi_test2() {
-    let value = [
-        [ ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', 'g', 'g', ],
-        [ ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', 'g', ],
-        [ ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', 'g', 'y', ],
-        [ ' ', ' ', ' ', 'y', 'r', 'r', 'r', 'b', 'g',  'g', 'g', 'y', 'g', ],
-        [ ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', 'y', 'g', ],
-        [ ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', ' ', 'b', 'b', 'g', ],
-    ];
-    let field = FieldNaiveBit::from_char(value);
+    let (field, info) =
+        parse_puzzle::<FieldNaiveBit>(include_str!("../puzzles/multi_test2.txt")).unwrap();
     println!("{}", field);
-    let mut next = crate::naive_next_puyo::NaiveNextPuyo::new();
-    next.len = 6;
-    next.value[0][0] = 3;
-    next.value[0][1] = 5;
-    next.value[1][0] = 4;
-    next.value[1][1] = 2;
-    next.value[2][0] = 5;
-    next.value[2][1] = 5;
-    next.value[3][0] = 2;
-    next.value[3][1] = 2;
-    next.value[4][0] = 5;
-    next.value[4][1] = 2;
-    next.value[5][0] = 4;
-    next.value[5][1] = 2;
-    let info = NazopuyoInfo { chain: 8, next };
     let mut solver = NoneRecursiveSolver::new(field, info);
-    let res = solver.solve_multi();
-    println!("{}", res.unwrap());
+    match solver.solve_multi() {
+        Some(res) => println!("{}", res),
+        None => println!("{}", solver.explain(&SolveOptions::default())),
+    }
 }
 
 #[allow(unused)]
 pub fn multi_test3() {
-    let value = [
-        [ ' ', ' ', ' ', ' ', ' ', ' ', ' ', 'g', 'g', 'g', 'b', 'b', 'g', ],
-        [ ' ', ' ', ' ', ' ', ' ', ' ', ' ', 'b', 'b', 'r', 'r', 'b', 'g', ],
-        [ ' ', ' ', ' ', ' ', ' ', ' ', ' ', 'b', 'y', 'r', 'y', 'y', 'g', ],
-        [ ' ', ' ', ' ', ' ', ' ', ' ', ' ', 'g', 'y', 'y', 'r', 'y', 'b', ],
-        [ ' ', ' ', ' ', ' ', ' ', ' ', ' ', 'g', 'b', 'r', 'r', 'b', 'b', ],
-        [ ' ', ' ', ' ', ' ', ' ', ' ', ' ', 'g', 'b', 'b', 'g', 'g', 'g', ],
-    ];
-    let field = FieldNaiveBit::from_char(value);
+    let (field, info) =
+        parse_puzzle::<FieldNaiveBit>(include_str!("../puzzles/multi_test3.txt")).unwrap();
     println!("{}", field);
-    let mut next = crate::naive_next_puyo::NaiveNextPuyo::new();
-    next.len = 7;
-    next.value[0][0] = 2;
-    next.value[0][1] = 3;
-    next.value[1][0] = 2;
-    next.value[1][1] = 4;
-    next.value[2][0] = 5;
-    next.value[2][1] = 5;
-    next.value[3][0] = 3;
-    next.value[3][1] = 4;
-    next.value[4][0] = 3;
-    next.value[4][1] = 4;
-    next.value[5][0] = 3;
-    next.value[5][1] = 3;
-    next.value[6][0] = 4;
-    next.value[6][1] = 4;
-    let info = NazopuyoInfo { chain: 12, next };
     let mut solver = NoneRecursiveSolver::new(field, info);
-    let res = solver.solve_multi();
-    println!("{}", res.unwrap());
+    match solver.solve_multi() {
+        Some(res) => println!("{}", res),
+        None => println!("{}", solver.explain(&SolveOptions::default())),
+    }
 }
 
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::cell::Cell;
     use crate::naive_next_puyo::NaiveNextPuyo;
     #[test]
     fn it_works() {
         let mut field = NaiveField::new();
-        field.set(12, 1, 1);
-        field.set(12, 2, 2);
-        field.set(12, 3, 2);
-        field.set(12, 4, 2);
-        field.set(12, 5, 4);
-        field.set(11, 1, 1);
-        field.set(11, 2, 1);
-        field.set(11, 3, 1);
-        field.set(11, 4, 4);
-        field.set(11, 5, 4);
-        field.set(10, 3, 3);
-        field.set(10, 4, 3);
+        field.set(12, 1, Cell::Ojama);
+        field.set(12, 2, Cell::RED);
+        field.set(12, 3, Cell::RED);
+        field.set(12, 4, Cell::RED);
+        field.set(12, 5, Cell::GREEN);
+        field.set(11, 1, Cell::Ojama);
+        field.set(11, 2, Cell::Ojama);
+        field.set(11, 3, Cell::Ojama);
+        field.set(11, 4, Cell::GREEN);
+        field.set(11, 5, Cell::GREEN);
+        field.set(10, 3, Cell::BLUE);
+        field.set(10, 4, Cell::BLUE);
         println!("{}", field);
-        let mut next = NaiveNextPuyo::new();
-        next.len = 2;
-        next.value[0][0] = 3;
-        next.value[0][1] = 4;
-        next.value[1][0] = 3;
-        next.value[1][1] = 2;
-        let info = NazopuyoInfo { chain: 3, next };
-        let mut solver = NoneRecursiveSolver::new(field, info);
+        let next = NaiveNextPuyo::from_vec(vec![[3, 4], [3, 2]]).unwrap();
+        let info = NazopuyoInfo::new(3, next);
+        let mut solver = NoneRecursiveSolver::new(field.clone(), info.clone());
         let res = solver.solve();
         assert!(res.is_some());
+
+        let mut solver = NoneRecursiveSolver::new(field, info);
+        solver.add_pruner(crate::pruning::ChainUpperBound);
+        assert_eq!(solver.solve(), res);
+    }
+
+    #[test]
+    fn give_up() {
+        use crate::solve_options::{CancelToken, GiveUpReason};
+        let mut field = NaiveField::new();
+        field.set(12, 0, Cell::RED);
+        let next = NaiveNextPuyo::from_vec(vec![[3, 4], [3, 2]]).unwrap();
+        let info = NazopuyoInfo::new(1, next);
+
+        let mut solver = NoneRecursiveSolver::new(field.clone(), info.clone());
+        let res = solver.solve_with(&SolveOptions::new().max_nodes(3));
+        assert_eq!(res, SolveResult::GaveUp(GiveUpReason::NodeLimit));
+
+        let token = CancelToken::new();
+        token.cancel();
+        let mut solver = NoneRecursiveSolver::new(field, info);
+        let res = solver.solve_with(&SolveOptions::new().cancel(token));
+        assert_eq!(res, SolveResult::GaveUp(GiveUpReason::Cancelled));
+    }
+
+    #[test]
+    fn prune_hopeless_root() {
+        use crate::pruning::ChainUpperBound;
+        let mut field = NaiveField::new();
+        field.set(12, 0, Cell::RED);
+        let next = NaiveNextPuyo::from_vec(vec![[3, 4], [3, 2]]).unwrap();
+        // どう置いても 4 個そろう色が無い
+        let info = NazopuyoInfo::new(1, next);
+        let mut solver = NoneRecursiveSolver::new(field, info);
+        solver.add_pruner(ChainUpperBound);
+        let res = solver.solve_with(&SolveOptions::new().max_nodes(1));
+        assert_eq!(res, SolveResult::NoSolution);
     }
 }
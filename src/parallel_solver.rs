@@ -0,0 +1,325 @@
+use std::collections::HashSet;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+use crate::field::{self, Field};
+use crate::nazopuyo_info::NazopuyoInfo;
+use crate::placement::for_each_move;
+use crate::progress::count_first_ply;
+use crate::pruning::{any_prune, Pruner};
+use crate::solve_options::{Budget, GiveUpReason, SolveOptions, SolveResult};
+
+/// スレッド間で共有する置換表
+/// ロックの取り合いを減らすため、盤面のハッシュ値でシャードに分けて持つ
+pub struct TranspositionTable<F> {
+    shards: Vec<Mutex<HashSet<F>>>,
+}
+
+impl<F> TranspositionTable<F>
+where
+    F: Clone + Eq + Hash,
+{
+    pub fn new(shards: usize) -> Self {
+        Self {
+            shards: (0..shards.max(1)).map(|_| Mutex::new(HashSet::new())).collect(),
+        }
+    }
+
+    fn shard(&self, field: &F) -> &Mutex<HashSet<F>> {
+        let mut hasher = DefaultHasher::new();
+        field.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % self.shards.len()]
+    }
+
+    /// 未登録なら登録して true を返す
+    pub fn insert(&self, field: &F) -> bool {
+        let mut shard = self.shard(field).lock().unwrap();
+        if shard.contains(field) {
+            return false;
+        }
+        shard.insert(field.clone())
+    }
+
+    pub fn contains(&self, field: &F) -> bool {
+        self.shard(field).lock().unwrap().contains(field)
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|s| s.lock().unwrap().len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// 使えるコア数
+pub fn default_workers() -> usize {
+    thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+/// (何手置いたか, 盤面, どの初手の下か (根は None))
+type Task<F> = (u32, F, Option<usize>);
+
+struct PoolState<F> {
+    tasks: Vec<Task<F>>,
+    idle: usize,
+}
+
+/// ワーカー間で共有する状態
+/// 暇なワーカーがいると、探索中のワーカーが自分のスタックの浅い方を分けてくれる
+struct WorkPool<'a, F: Field> {
+    info: NazopuyoInfo,
+    workers: usize,
+    state: Mutex<PoolState<F>>,
+    available: Condvar,
+    hungry: AtomicUsize,
+    stop: AtomicBool,
+    result: Mutex<Option<F>>,
+    gave_up: Mutex<Option<GiveUpReason>>,
+    table: TranspositionTable<F>,
+    budget: Budget,
+    pruners: &'a [Box<dyn Pruner<F>>],
+}
+
+impl<F> WorkPool<'_, F>
+where
+    F: Field + Clone + Eq + Hash + Send,
+{
+    fn pop_task(&self) -> Option<Task<F>> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if self.stop.load(Ordering::Relaxed) {
+                return None;
+            }
+            if let Some(task) = state.tasks.pop() {
+                return Some(task);
+            }
+            state.idle += 1;
+            self.hungry.fetch_add(1, Ordering::Relaxed);
+            if state.idle == self.workers {
+                // 全員暇 => 探索し尽くした
+                self.stop.store(true, Ordering::Relaxed);
+                self.available.notify_all();
+                return None;
+            }
+            state = self.available.wait(state).unwrap();
+            state.idle -= 1;
+            self.hungry.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    /// スタックの浅い方半分を共有キューに移す
+    fn share(&self, stack: &mut Vec<Task<F>>) {
+        let n = stack.len() / 2;
+        let mut state = self.state.lock().unwrap();
+        state.tasks.extend(stack.drain(..n));
+        self.available.notify_all();
+    }
+
+    fn finish(&self, field: F) {
+        let mut result = self.result.lock().unwrap();
+        if result.is_none() {
+            *result = Some(field);
+        }
+        self.stop_all();
+    }
+
+    fn give_up(&self, reason: GiveUpReason) {
+        let mut gave_up = self.gave_up.lock().unwrap();
+        if gave_up.is_none() {
+            *gave_up = Some(reason);
+        }
+        self.stop_all();
+    }
+
+    fn stop_all(&self) {
+        let _state = self.state.lock().unwrap();
+        self.stop.store(true, Ordering::Relaxed);
+        self.available.notify_all();
+    }
+
+    fn run(&self) {
+        let mut stack = vec![];
+        let mut children = vec![];
+        while let Some(task) = self.pop_task() {
+            stack.push(task);
+            while let Some((depth, mut field, branch)) = stack.pop() {
+                if self.stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                if let Some(reason) = self.budget.visit(depth) {
+                    self.give_up(reason);
+                    return;
+                }
+                if depth == self.info.next.len() as u32 {
+                    let f2 = field.clone();
+                    if field::chain_with(&mut field, &self.info.rules) == self.info.chain {
+                        self.finish(f2);
+                        return;
+                    }
+                    self.budget.expanded(branch, 0);
+                    continue;
+                }
+                if any_prune(self.pruners, &field, &self.info, depth) {
+                    self.budget.expanded(branch, 0);
+                    continue;
+                }
+
+                expand(&mut field, &self.info, depth, &mut children);
+                let mut pushed = 0;
+                for child in children.drain(..).rev() {
+                    let inserted = self.table.insert(&child);
+                    self.budget.lookup(!inserted);
+                    if inserted {
+                        let branch = branch.unwrap_or_else(|| self.budget.open_first_ply());
+                        stack.push((depth + 1, child, Some(branch)));
+                        pushed += 1;
+                    }
+                }
+                self.budget.expanded(branch, pushed);
+
+                if self.hungry.load(Ordering::Relaxed) > 0 && stack.len() > 1 {
+                    self.share(&mut stack);
+                }
+            }
+        }
+    }
+}
+
+/// depth 手目のツモの置き方を全部試して、探索を続ける盤面を children に積む
+/// 順番は Solver::dfs と同じ
+pub(crate) fn expand<F: Field + Clone>(field: &mut F, info: &NazopuyoInfo, depth: u32, children: &mut Vec<F>) {
+    let pair = info.next.pair(depth as usize);
+    let _ = for_each_move(field, pair, &info.rules, |_, child| {
+        if depth + 1 == info.next.len() as u32
+            || (field::is_alive_with(child, &info.rules) && !field::has_chain_with(child, &info.rules))
+        {
+            children.push(child.clone());
+        }
+        ControlFlow::<()>::Continue(())
+    });
+}
+
+/// workers 本のスレッドで探索する
+/// 置換表は全スレッドで共有し、どれかが解を見つけるか打ち切り条件に当たったら全員止まる
+pub fn solve_parallel<F>(
+    field: F,
+    info: NazopuyoInfo,
+    workers: usize,
+    options: &SolveOptions,
+    pruners: &[Box<dyn Pruner<F>>],
+) -> SolveResult<F>
+where
+    F: Field + Clone + Eq + Hash + Send,
+{
+    let workers = workers.max(1);
+    let budget = Budget::new(options, info.next.len());
+    budget.set_first_ply_total(count_first_ply(&field, &info, 0));
+    let pool = WorkPool {
+        info,
+        workers,
+        state: Mutex::new(PoolState {
+            tasks: vec![(0, field, None)],
+            idle: 0,
+        }),
+        available: Condvar::new(),
+        hungry: AtomicUsize::new(0),
+        stop: AtomicBool::new(false),
+        result: Mutex::new(None),
+        gave_up: Mutex::new(None),
+        table: TranspositionTable::new(workers * 16),
+        budget,
+        pruners,
+    };
+
+    thread::scope(|s| {
+        for _ in 0..workers {
+            s.spawn(|| pool.run());
+        }
+    });
+    pool.budget.finish();
+
+    // 解が見つかったなら、同時に打ち切られていても解を返す
+    match (pool.result.into_inner().unwrap(), pool.gave_up.into_inner().unwrap()) {
+        (Some(field), _) => SolveResult::Solved(field),
+        (None, Some(reason)) => SolveResult::GaveUp(reason),
+        (None, None) => SolveResult::NoSolution,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cell::Cell;
+    use crate::field_naive_bit::FieldNaiveBit;
+    use crate::naive_field::NaiveField;
+    use crate::naive_next_puyo::NaiveNextPuyo;
+
+    fn small_puzzle() -> (NaiveField, NazopuyoInfo) {
+        let mut field = NaiveField::new();
+        field.set(12, 1, Cell::Ojama);
+        field.set(12, 2, Cell::RED);
+        field.set(12, 3, Cell::RED);
+        field.set(12, 4, Cell::RED);
+        field.set(12, 5, Cell::GREEN);
+        field.set(11, 1, Cell::Ojama);
+        field.set(11, 2, Cell::Ojama);
+        field.set(11, 3, Cell::Ojama);
+        field.set(11, 4, Cell::GREEN);
+        field.set(11, 5, Cell::GREEN);
+        field.set(10, 3, Cell::BLUE);
+        field.set(10, 4, Cell::BLUE);
+        let next = NaiveNextPuyo::from_vec(vec![[3, 4], [3, 2]]).unwrap();
+        (field, NazopuyoInfo::new(3, next))
+    }
+
+    #[test]
+    fn finds_solution() {
+        let (field, info) = small_puzzle();
+        for workers in [1, 4] {
+            let res = solve_parallel(field.clone(), info.clone(), workers, &SolveOptions::default(), &[])
+                .solution()
+                .unwrap();
+            let mut res2 = res.clone();
+            assert_eq!(field::chain(&mut res2), 3);
+        }
+    }
+
+    #[test]
+    fn stops_when_no_solution() {
+        let (field, mut info) = small_puzzle();
+        info.chain = 5;
+        assert_eq!(
+            solve_parallel(field, info, 4, &SolveOptions::default(), &[]),
+            SolveResult::NoSolution
+        );
+    }
+
+    #[test]
+    fn gives_up_on_node_limit() {
+        let (field, mut info) = small_puzzle();
+        info.chain = 5;
+        let options = SolveOptions::new().max_nodes(5);
+        assert_eq!(
+            solve_parallel(field, info, 4, &options, &[]),
+            SolveResult::GaveUp(GiveUpReason::NodeLimit)
+        );
+    }
+
+    #[test]
+    fn table_is_shared() {
+        let table = TranspositionTable::new(4);
+        let field = FieldNaiveBit::new();
+        assert!(table.insert(&field));
+        assert!(!table.insert(&field));
+        assert!(table.contains(&field));
+        assert_eq!(table.len(), 1);
+    }
+}
@@ -0,0 +1,518 @@
+//! 置き方 (列と向き) と、解答の検証
+//!
+//! 置き方は ソルバーと同じ落とし方で盤面に置く
+//! - 縦置きはどちらかを先に落として、もう片方をその上に積む
+//! - 横置きはそれぞれの列に落とす (ちぎり)
+//!
+//! 置けるかどうかの判定 (place_with) はソルバーの手の生成 (for_each_move) と共通で、実際のゲームに合わせる
+//! - 出てくる列 (3 列目) から置く列までの間に 12 段目まで埋まった列があると届かない
+//! - クイックターンの無いルールでは、両隣がふさがった列で子ぷよを下にできない
+//!
+//! 文字では `<列 (1 始まり)><向き>` で書く。向きは子ぷよのある側で `u` 上, `r` 右, `d` 下, `l` 左
+//! 例: `3u 4r 1l`。読むときは盤面の幅を知らないので、列が盤面に収まるかは置くとき (place_with, verify) に見る
+
+use std::fmt;
+use std::ops::ControlFlow;
+use std::str::FromStr;
+
+use crate::cell::Cell;
+use crate::field::{self, get_top, Field};
+use crate::nazopuyo_info::NazopuyoInfo;
+use crate::rules::Rules;
+
+/// ぷよが出てくる列 (左から 3 列目)
+const SPAWN_COLUMN: usize = 2;
+
+/// 軸ぷよから見た子ぷよの位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Rotation {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+/// x は軸ぷよの列 (0 始まり)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Placement {
+    pub x: usize,
+    pub rotation: Rotation,
+}
+
+impl Placement {
+    pub fn new(x: usize, rotation: Rotation) -> Self {
+        Self { x, rotation }
+    }
+
+    /// 6 列の盤面に収まる置き方すべて (22 通り)
+    pub fn all() -> impl Iterator<Item = Placement> {
+        Self::all_in(6)
+    }
+
+    /// width 列の盤面に収まる置き方すべて
+    pub fn all_in(width: usize) -> impl Iterator<Item = Placement> {
+        let rotations = [Rotation::Up, Rotation::Right, Rotation::Down, Rotation::Left];
+        (0..width)
+            .flat_map(move |x| rotations.map(|r| Placement::new(x, r)))
+            .filter(move |p| p.child_x_in(width).is_some())
+    }
+
+    /// 6 列の盤面での子ぷよの列
+    pub fn child_x(&self) -> Option<usize> {
+        self.child_x_in(6)
+    }
+
+    /// width 列の盤面での子ぷよの列。はみ出すなら None
+    pub fn child_x_in(&self, width: usize) -> Option<usize> {
+        match self.rotation {
+            Rotation::Up | Rotation::Down => Some(self.x),
+            Rotation::Right => self.x.checked_add(1).filter(|&x| x < width),
+            Rotation::Left => self.x.checked_sub(1),
+        }
+    }
+}
+
+impl fmt::Display for Placement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let r = match self.rotation {
+            Rotation::Up => 'u',
+            Rotation::Right => 'r',
+            Rotation::Down => 'd',
+            Rotation::Left => 'l',
+        };
+        write!(f, "{}{}", self.x + 1, r)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsePlacementError(pub String);
+
+impl fmt::Display for ParsePlacementError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bad placement {:?}, expected a column number from 1 and one of u r d l", self.0)
+    }
+}
+
+impl std::error::Error for ParsePlacementError {}
+
+impl FromStr for Placement {
+    type Err = ParsePlacementError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParsePlacementError(s.to_string());
+        let mut chars = s.chars();
+        let rotation = match chars.next_back() {
+            Some('u') => Rotation::Up,
+            Some('r') => Rotation::Right,
+            Some('d') => Rotation::Down,
+            Some('l') => Rotation::Left,
+            _ => return Err(err()),
+        };
+        let column = chars.as_str();
+        if !column.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(err());
+        }
+        let x = match column.parse::<usize>() {
+            Ok(d) if d >= 1 => d - 1,
+            _ => return Err(err()),
+        };
+        let placement = Placement::new(x, rotation);
+        // 左端より左はどの盤面でも置けない。右端は盤面の幅で決まるので置くときに見る
+        placement.child_x_in(usize::MAX).ok_or_else(err)?;
+        Ok(placement)
+    }
+}
+
+/// 空白区切りの置き方を読む
+pub fn parse_placements(s: &str) -> Result<Vec<Placement>, ParsePlacementError> {
+    s.split_whitespace().map(|p| p.parse()).collect()
+}
+
+/// その手が置けない理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum IllegalMove {
+    /// 盤面の外
+    OutOfRange,
+    /// 置く列に空きが無い (13 段目まで埋まっている)
+    ColumnFull,
+    /// 出てくる列から置く列までの間に 12 段目まで埋まった列がある
+    Unreachable,
+    /// クイックターンが無く、子ぷよを下に回せない
+    CannotFlip,
+    /// 最後の手より前に連鎖が起きた
+    EarlyChain,
+    /// 置いたら窒息した
+    Dead,
+}
+
+impl fmt::Display for IllegalMove {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IllegalMove::OutOfRange => write!(f, "placement is outside the field"),
+            IllegalMove::ColumnFull => write!(f, "column full"),
+            IllegalMove::Unreachable => write!(f, "column unreachable"),
+            IllegalMove::CannotFlip => write!(f, "cannot turn the pair upside down without quick turn"),
+            IllegalMove::EarlyChain => write!(f, "chain started before the last piece"),
+            IllegalMove::Dead => write!(f, "field is dead"),
+        }
+    }
+}
+
+/// ぷよぷよ通のルールで 1 組置く。置けなければ盤面は変えない
+/// 連鎖や窒息は見ない (verify が見る)
+pub fn place<F: Field>(field: &mut F, pair: [Cell; 2], placement: Placement) -> Result<(), IllegalMove> {
+    place_with(field, pair, placement, &Rules::TSU)
+}
+
+/// rules で 1 組置く
+pub fn place_with<F: Field>(
+    field: &mut F,
+    pair: [Cell; 2],
+    placement: Placement,
+    rules: &Rules,
+) -> Result<(), IllegalMove> {
+    drop_pair(field, pair, placement, rules).map(|_| ())
+}
+
+/// place_with の中身。置いた 2 マス (段, 列) を返す
+fn drop_pair<F: Field>(
+    field: &mut F,
+    pair: [Cell; 2],
+    placement: Placement,
+    rules: &Rules,
+) -> Result<[(usize, usize); 2], IllegalMove> {
+    let x = placement.x;
+    let child_x = match placement.child_x_in(F::WIDTH) {
+        Some(c) if x < F::WIDTH => c,
+        _ => return Err(IllegalMove::OutOfRange),
+    };
+
+    // 軸ぷよが通る列 (出てくる列と置く列は除く)
+    let (lo, hi) = if x < SPAWN_COLUMN { (x + 1, SPAWN_COLUMN) } else { (SPAWN_COLUMN + 1, x) };
+    if (lo..hi).any(|c| !field.get(1, c).is_empty()) {
+        return Err(IllegalMove::Unreachable);
+    }
+    if placement.rotation == Rotation::Down && !field::can_flip(field, rules, x) {
+        return Err(IllegalMove::CannotFlip);
+    }
+
+    // 下に来るほうから落とす
+    let (first, second) = match placement.rotation {
+        Rotation::Down => ((child_x, pair[1]), (x, pair[0])),
+        _ => ((x, pair[0]), (child_x, pair[1])),
+    };
+    let y1 = drop_row(field, first.0).ok_or(IllegalMove::ColumnFull)?;
+    field.set(y1, first.0, field::landing(rules, y1, first.1));
+    match drop_row(field, second.0) {
+        Some(y2) => {
+            field.set(y2, second.0, field::landing(rules, y2, second.1));
+            Ok([(y1, first.0), (y2, second.0)])
+        }
+        None => {
+            field.set(y1, first.0, Cell::Empty);
+            Err(IllegalMove::ColumnFull)
+        }
+    }
+}
+
+/// pair の置ける置き方を全部試す。置けるかどうかは place_with と同じ
+/// 順番は列が column_order、各列で 上, 右, 下, 右隣の列から左
+/// visit には置き方と置いたあとの盤面を渡し、呼び終わったら field は元に戻す
+/// visit が Break を返したらそこでやめる
+pub(crate) fn for_each_move<F: Field, B>(
+    field: &mut F,
+    pair: [Cell; 2],
+    rules: &Rules,
+    mut visit: impl FnMut(Placement, &F) -> ControlFlow<B>,
+) -> ControlFlow<B> {
+    for x in field::column_order::<F>() {
+        let placements = [
+            Placement::new(x, Rotation::Up),
+            Placement::new(x, Rotation::Right),
+            Placement::new(x, Rotation::Down),
+            Placement::new(x + 1, Rotation::Left),
+        ];
+        for placement in placements {
+            let Ok(cells) = drop_pair(field, pair, placement, rules) else {
+                continue;
+            };
+            let flow = visit(placement, field);
+            for (y, x) in cells {
+                field.set(y, x, Cell::Empty);
+            }
+            flow?;
+        }
+    }
+    ControlFlow::Continue(())
+}
+
+/// その列に落としたときの段
+pub(crate) fn drop_row<F: Field>(field: &F, x: usize) -> Option<usize> {
+    match get_top(field, x) {
+        Some(0) => None,
+        Some(y) => Some(y - 1),
+        None => Some(F::HEIGHT - 1),
+    }
+}
+
+/// step 手目 (0 始まり) のネクストを置く
+/// ソルバーと同じく 最後の手より前は 連鎖も窒息も許さない。置けなければ盤面は変えない
+pub fn play<F: Field + Clone>(
+    field: &mut F,
+    info: &NazopuyoInfo,
+    step: usize,
+    placement: Placement,
+) -> Result<(), IllegalMove> {
+    if step >= info.next.len() {
+        return Err(IllegalMove::OutOfRange);
+    }
+    let mut placed = field.clone();
+    place_with(&mut placed, info.next.pair(step), placement, &info.rules)?;
+    if step + 1 < info.next.len() {
+        if field::has_chain_with(&placed, &info.rules) {
+            return Err(IllegalMove::EarlyChain);
+        }
+        if !field::is_alive_with(&placed, &info.rules) {
+            return Err(IllegalMove::Dead);
+        }
+    }
+    *field = placed;
+    Ok(())
+}
+
+/// 検証の結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "status", rename_all = "snake_case"))]
+pub enum Verdict {
+    /// 条件どおりの連鎖が起きた
+    Cleared { chain: u32 },
+    /// 最後まで置けたが連鎖数が違う
+    WrongChain { expected: u32, actual: u32 },
+    /// step 手目 (0 始まり) が置けなかった
+    Illegal { step: usize, reason: IllegalMove },
+    /// 手数がネクストの数と違う
+    WrongLength { expected: usize, actual: usize },
+}
+
+impl Verdict {
+    pub fn is_cleared(&self) -> bool {
+        matches!(self, Verdict::Cleared { .. })
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Verdict::Cleared { chain } => write!(f, "cleared with {} chain", chain),
+            Verdict::WrongChain { expected, actual } => {
+                write!(f, "wrong chain: expected {}, got {}", expected, actual)
+            }
+            Verdict::Illegal { step, reason } => write!(f, "step {}: {}", step + 1, reason),
+            Verdict::WrongLength { expected, actual } => {
+                write!(f, "expected {} placements, got {}", expected, actual)
+            }
+        }
+    }
+}
+
+/// 解答を最初から置いていって、問題の条件を満たすか調べる
+pub fn verify<F: Field + Clone>(field: &F, info: &NazopuyoInfo, placements: &[Placement]) -> Verdict {
+    if placements.len() != info.next.len() {
+        return Verdict::WrongLength {
+            expected: info.next.len(),
+            actual: placements.len(),
+        };
+    }
+    let mut field = field.clone();
+    for (step, &placement) in placements.iter().enumerate() {
+        if let Err(reason) = play(&mut field, info, step, placement) {
+            return Verdict::Illegal { step, reason };
+        }
+    }
+    let actual = field::chain_with(&mut field, &info.rules);
+    if actual == info.chain {
+        Verdict::Cleared { chain: actual }
+    } else {
+        Verdict::WrongChain {
+            expected: info.chain,
+            actual,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::naive_field::{NaiveField, NaiveFieldOf};
+    use crate::puzzle_text::parse_puzzle;
+
+    fn puzzle(rows: &[&str], next: &str, chain: u32) -> (NaiveField, NazopuyoInfo) {
+        let mut text = "......\n".repeat(13 - rows.len());
+        for row in rows {
+            text.push_str(row);
+            text.push('\n');
+        }
+        text.push_str(&format!("next: {}\nchain: {}\n", next, chain));
+        parse_puzzle(&text).unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        let err = parse_placements("3u 4r 1l 6d").unwrap_err();
+        assert_eq!(err, ParsePlacementError("1l".to_string()));
+        let placements = parse_placements(" 3u  6l 1d ").unwrap();
+        assert_eq!(
+            placements,
+            vec![
+                Placement::new(2, Rotation::Up),
+                Placement::new(5, Rotation::Left),
+                Placement::new(0, Rotation::Down),
+            ]
+        );
+        assert_eq!(placements[1].to_string(), "6l");
+        for bad in ["3", "u", "0u", "+3u", "3x", "3uu", "1l"] {
+            assert_eq!(bad.parse::<Placement>(), Err(ParsePlacementError(bad.to_string())));
+        }
+        // 盤面の幅はここでは見ない
+        assert_eq!("7u".parse(), Ok(Placement::new(6, Rotation::Up)));
+        assert_eq!("6r".parse(), Ok(Placement::new(5, Rotation::Right)));
+        assert_eq!("10l".parse::<Placement>().map(|p| p.to_string()), Ok("10l".to_string()));
+        assert_eq!(Placement::all().count(), 22);
+        assert_eq!(Placement::all_in(8).count(), 30);
+    }
+
+    #[test]
+    fn place_rotations() {
+        let mut field = NaiveField::new();
+        place(&mut field, [Cell::RED, Cell::BLUE], Placement::new(0, Rotation::Up)).unwrap();
+        assert_eq!((field.get(12, 0), field.get(11, 0)), (Cell::RED, Cell::BLUE));
+        place(&mut field, [Cell::RED, Cell::BLUE], Placement::new(1, Rotation::Down)).unwrap();
+        assert_eq!((field.get(12, 1), field.get(11, 1)), (Cell::BLUE, Cell::RED));
+        place(&mut field, [Cell::GREEN, Cell::YELLOW], Placement::new(1, Rotation::Right)).unwrap();
+        assert_eq!((field.get(10, 1), field.get(12, 2)), (Cell::GREEN, Cell::YELLOW));
+        place(&mut field, [Cell::GREEN, Cell::YELLOW], Placement::new(4, Rotation::Left)).unwrap();
+        assert_eq!((field.get(12, 4), field.get(12, 3)), (Cell::GREEN, Cell::YELLOW));
+    }
+
+    #[test]
+    fn verdicts() {
+        let (field, info) = puzzle(&["rrr..."], "rb", 1);
+        let ok = parse_placements("4u").unwrap();
+        assert_eq!(verify(&field, &info, &ok), Verdict::Cleared { chain: 1 });
+        let miss = parse_placements("4d").unwrap();
+        assert_eq!(
+            verify(&field, &info, &miss),
+            Verdict::WrongChain { expected: 1, actual: 0 }
+        );
+        assert_eq!(
+            verify(&field, &info, &[]),
+            Verdict::WrongLength { expected: 1, actual: 0 }
+        );
+
+        let (field, info) = puzzle(&["rrr..."], "rb bb", 1);
+        let early = parse_placements("4u 6u").unwrap();
+        assert_eq!(
+            verify(&field, &info, &early),
+            Verdict::Illegal { step: 0, reason: IllegalMove::EarlyChain }
+        );
+    }
+
+    #[test]
+    fn illegal_moves() {
+        // 1 列目は 13 段目まで、2 列目は 12 段目まで埋まっている
+        let mut rows = vec!["r....."];
+        rows.extend(["rb....", "br...."].repeat(6));
+        let (field, info) = puzzle(&rows, "gy gy", 1);
+        let v = verify(&field, &info, &parse_placements("1u 4u").unwrap());
+        assert_eq!(v, Verdict::Illegal { step: 0, reason: IllegalMove::Unreachable });
+        let v = verify(&field, &info, &parse_placements("2u 4u").unwrap());
+        assert_eq!(v, Verdict::Illegal { step: 0, reason: IllegalMove::ColumnFull });
+        let v = verify(&field, &info, &parse_placements("2l 4u").unwrap());
+        assert_eq!(v, Verdict::Illegal { step: 0, reason: IllegalMove::ColumnFull });
+
+        // 3 列目を 11 段まで積んでから 2 個置くと窒息
+        let rows = ["..y...", "..g...", "..y...", "..g...", "..y...", "..g...", "..y...", "..g...", "..y...", "..g...", "..y..."];
+        let (field, info) = puzzle(&rows, "rb rb", 1);
+        let v = verify(&field, &info, &parse_placements("3u 1u").unwrap());
+        assert_eq!(v, Verdict::Illegal { step: 0, reason: IllegalMove::Dead });
+    }
+
+    #[test]
+    fn outside_the_field() {
+        // 6 列の盤面ではみ出す置き方は置くときに弾く
+        let (field, info) = puzzle(&["rrr..."], "gy", 1);
+        for p in ["7u", "6r", "12d"] {
+            let v = verify(&field, &info, &parse_placements(p).unwrap());
+            assert_eq!(v, Verdict::Illegal { step: 0, reason: IllegalMove::OutOfRange });
+        }
+
+        // 8 列の盤面なら 7 列目にも置ける
+        let mut wide = NaiveFieldOf::<8, 13>::new();
+        place(&mut wide, [Cell::GREEN, Cell::YELLOW], "7r".parse().unwrap()).unwrap();
+        assert_eq!((wide.get(12, 6), wide.get(12, 7)), (Cell::GREEN, Cell::YELLOW));
+        let p = "8r".parse().unwrap();
+        assert_eq!(place(&mut wide, [Cell::GREEN, Cell::YELLOW], p), Err(IllegalMove::OutOfRange));
+    }
+
+    #[test]
+    fn same_moves_as_solver() {
+        // 2 列目が 12 段目まで、5 列目が 13 段目まで埋まっている
+        let mut field = NaiveField::new();
+        for y in 1..13 {
+            field.set(y, 1, if y % 2 == 0 { Cell::GREEN } else { Cell::YELLOW });
+        }
+        for y in 0..13 {
+            field.set(y, 4, if y % 2 == 0 { Cell::GREEN } else { Cell::YELLOW });
+        }
+        let pair = [Cell::RED, Cell::BLUE];
+        for rules in [Rules::TSU, Rules::CLASSIC] {
+            let mut generated = vec![];
+            let _ = for_each_move(&mut field.clone(), pair, &rules, |p, _| {
+                generated.push(p);
+                ControlFlow::<()>::Continue(())
+            });
+            generated.sort_by_key(|p| (p.x, p.rotation as u8));
+            let legal: Vec<_> = Placement::all()
+                .filter(|&p| place_with(&mut field.clone(), pair, p, &rules).is_ok())
+                .collect();
+            assert_eq!(generated, legal);
+            assert!(!legal.iter().any(|p| p.x == 0));
+        }
+    }
+
+    #[test]
+    fn classic_rules() {
+        // 1 列目と 3 列目が 12 段目まで埋まった谷
+        let mut well = NaiveField::new();
+        for y in 1..13 {
+            let cell = if y % 2 == 0 { Cell::GREEN } else { Cell::YELLOW };
+            well.set(y, 0, cell);
+            well.set(y, 2, cell);
+        }
+        let pair = [Cell::RED, Cell::BLUE];
+        let down = Placement::new(1, Rotation::Down);
+        assert!(place(&mut well.clone(), pair, down).is_ok());
+        assert_eq!(
+            place_with(&mut well.clone(), pair, down, &Rules::CLASSIC),
+            Err(IllegalMove::CannotFlip)
+        );
+        assert!(place_with(&mut well.clone(), pair, Placement::new(1, Rotation::Up), &Rules::CLASSIC).is_ok());
+
+        // 13 段目に置いたぷよは消える
+        let mut tsu = NaiveField::new();
+        for y in 2..13 {
+            tsu.set(y, 4, if y % 2 == 0 { Cell::GREEN } else { Cell::YELLOW });
+        }
+        let mut classic = tsu.clone();
+        place(&mut tsu, pair, Placement::new(4, Rotation::Up)).unwrap();
+        place_with(&mut classic, pair, Placement::new(4, Rotation::Up), &Rules::CLASSIC).unwrap();
+        assert_eq!((tsu.get(1, 4), tsu.get(0, 4)), (Cell::RED, Cell::BLUE));
+        assert_eq!((classic.get(1, 4), classic.get(0, 4)), (Cell::RED, Cell::Empty));
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use crate::field::Field;
+use crate::nazopuyo_info::NazopuyoInfo;
+use crate::parallel_solver::expand;
+
+/// 探索の途中経過
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Progress {
+    /// 訪れたノード数
+    pub nodes: u64,
+    /// depth ごとに訪れたノード数
+    pub depth_histogram: Vec<u64>,
+    /// 置換表を引いた回数と、既に訪れていた回数
+    pub table_lookups: u64,
+    pub table_hits: u64,
+    /// 初手の候補数と、そのうち調べ終わった数
+    pub first_ply_total: u64,
+    pub first_ply_done: u64,
+    pub elapsed: Duration,
+    /// 探索が終わったかどうか (最後の報告だけ true)
+    pub finished: bool,
+}
+
+impl Progress {
+    pub fn hit_rate(&self) -> f64 {
+        if self.table_lookups == 0 {
+            return 0.0;
+        }
+        self.table_hits as f64 / self.table_lookups as f64
+    }
+
+    /// 初手単位で見た進み具合の目安 (0.0 ..= 1.0)
+    pub fn completion(&self) -> f64 {
+        if self.finished {
+            return 1.0;
+        }
+        if self.first_ply_total == 0 {
+            return 0.0;
+        }
+        (self.first_ply_done as f64 / self.first_ply_total as f64).min(1.0)
+    }
+}
+
+/// 途中経過を受け取る
+/// 並列探索ではどのワーカーからも呼ばれるので Sync が必要
+pub trait ProgressObserver: Send + Sync {
+    fn on_progress(&self, progress: &Progress);
+}
+
+/// 報告の間隔 (ノード数) の既定値
+pub const DEFAULT_PROGRESS_INTERVAL: u64 = 1 << 16;
+
+/// 探索中の統計
+/// 並列探索でも共有できるように全部アトミックに数える
+#[derive(Debug)]
+pub(crate) struct Stats {
+    start: Instant,
+    nodes: AtomicU64,
+    depth_histogram: Vec<AtomicU64>,
+    table_lookups: AtomicU64,
+    table_hits: AtomicU64,
+    first_ply_total: AtomicU64,
+    first_ply_done: AtomicU64,
+    /// スタックで探索するときの、初手ごとのまだ調べていないノード数
+    /// 並列探索では同じ初手の下を何人かで調べるので、0 になったら調べ終わり
+    first_ply_pending: OnceLock<Vec<AtomicU64>>,
+    next_first_ply: AtomicUsize,
+}
+
+impl Stats {
+    pub(crate) fn new(max_depth: usize) -> Self {
+        Self {
+            start: Instant::now(),
+            nodes: AtomicU64::new(0),
+            depth_histogram: (0..=max_depth).map(|_| AtomicU64::new(0)).collect(),
+            table_lookups: AtomicU64::new(0),
+            table_hits: AtomicU64::new(0),
+            first_ply_total: AtomicU64::new(0),
+            first_ply_done: AtomicU64::new(0),
+            first_ply_pending: OnceLock::new(),
+            next_first_ply: AtomicUsize::new(0),
+        }
+    }
+
+    /// ノードを一つ数えて、これまでのノード数を返す
+    pub(crate) fn visit(&self, depth: u32) -> u64 {
+        if let Some(count) = self.depth_histogram.get(depth as usize) {
+            count.fetch_add(1, Ordering::Relaxed);
+        }
+        self.nodes.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub(crate) fn nodes(&self) -> u64 {
+        self.nodes.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn lookup(&self, hit: bool) {
+        self.table_lookups.fetch_add(1, Ordering::Relaxed);
+        if hit {
+            self.table_hits.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn set_first_ply_total(&self, total: u64) {
+        self.first_ply_total.store(total, Ordering::Relaxed);
+        let _ = self
+            .first_ply_pending
+            .set((0..total).map(|_| AtomicU64::new(0)).collect());
+    }
+
+    pub(crate) fn first_ply_done(&self) {
+        self.first_ply_done.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 初手を 1 つ積んで、その番号を返す
+    pub(crate) fn open_first_ply(&self) -> usize {
+        let branch = self.next_first_ply.fetch_add(1, Ordering::Relaxed);
+        if let Some(pending) = self.first_ply_pending.get().and_then(|p| p.get(branch)) {
+            pending.store(1, Ordering::Relaxed);
+        }
+        branch
+    }
+
+    /// branch の初手の下のノードを 1 つ調べ終わり、その子を children 個積んだ
+    pub(crate) fn expanded(&self, branch: usize, children: u64) {
+        let pending = match self.first_ply_pending.get().and_then(|p| p.get(branch)) {
+            Some(pending) => pending,
+            None => return,
+        };
+        // 子を足してから自分を引くと途中で 0 にならないので、まとめて足し引きする
+        if children > 0 {
+            pending.fetch_add(children - 1, Ordering::AcqRel);
+        } else if pending.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.first_ply_done();
+        }
+    }
+
+    pub(crate) fn snapshot(&self, finished: bool) -> Progress {
+        Progress {
+            nodes: self.nodes.load(Ordering::Relaxed),
+            depth_histogram: self
+                .depth_histogram
+                .iter()
+                .map(|c| c.load(Ordering::Relaxed))
+                .collect(),
+            table_lookups: self.table_lookups.load(Ordering::Relaxed),
+            table_hits: self.table_hits.load(Ordering::Relaxed),
+            first_ply_total: self.first_ply_total.load(Ordering::Relaxed),
+            first_ply_done: self.first_ply_done.load(Ordering::Relaxed),
+            elapsed: self.start.elapsed(),
+            finished,
+        }
+    }
+}
+
+/// 初手の候補数 (同じ盤面になるものは一つと数える)
+pub(crate) fn count_first_ply<F>(field: &F, info: &NazopuyoInfo, depth: u32) -> u64
+where
+    F: Field + Clone + Eq + Hash,
+{
+    if depth >= info.next.len() as u32 {
+        return 0;
+    }
+    let mut children = vec![];
+    expand(&mut field.clone(), info, depth, &mut children);
+    children.into_iter().collect::<HashSet<_>>().len() as u64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cell::Cell;
+    use std::sync::{Arc, Mutex};
+
+    use crate::nazopuyo_solver::Solver;
+    use crate::naive_field::NaiveField;
+    use crate::naive_next_puyo::NaiveNextPuyo;
+    use crate::solve_options::SolveOptions;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<Progress>>);
+
+    impl ProgressObserver for Recorder {
+        fn on_progress(&self, progress: &Progress) {
+            self.0.lock().unwrap().push(progress.clone());
+        }
+    }
+
+    #[test]
+    fn reports_progress() {
+        let mut field = NaiveField::new();
+        field.set(12, 0, Cell::RED);
+        let next = NaiveNextPuyo::from_vec(vec![[3, 4], [3, 3]]).unwrap();
+        let info = NazopuyoInfo::new(1, next);
+
+        let recorder = Arc::new(Recorder::default());
+        let options = SolveOptions::new().progress(recorder.clone(), 10);
+        let mut solver = Solver::new(field, info);
+        solver.solve_with(&options);
+
+        let reports = recorder.0.lock().unwrap();
+        assert!(reports.len() > 1);
+        let last = reports.last().unwrap();
+        assert!(last.finished);
+        assert_eq!(last.completion(), 1.0);
+        assert_eq!(last.depth_histogram.iter().sum::<u64>(), last.nodes);
+        assert_eq!(last.depth_histogram[0], 1);
+        assert_eq!(last.first_ply_total, 22);
+        assert!(last.table_hits > 0);
+        assert!(reports.windows(2).all(|w| w[0].nodes <= w[1].nodes));
+    }
+
+    #[test]
+    fn counts_finished_first_plies() {
+        use crate::none_recursive_solver::NoneRecursiveSolver;
+        use crate::parallel_solver::solve_parallel;
+        use crate::solve_options::SolveResult;
+
+        let mut field = NaiveField::new();
+        field.set(12, 0, Cell::RED);
+        let next = NaiveNextPuyo::from_vec(vec![[3, 4], [3, 3]]).unwrap();
+        // 解が無いので最後まで調べる
+        let info = NazopuyoInfo::new(3, next);
+
+        let check = |recorder: &Recorder| {
+            let reports = recorder.0.lock().unwrap();
+            let last = reports.last().unwrap();
+            assert_eq!(last.first_ply_total, 22);
+            assert_eq!(last.first_ply_done, last.first_ply_total);
+            assert!(reports.windows(2).all(|w| w[0].first_ply_done <= w[1].first_ply_done));
+        };
+
+        let recorder = Arc::new(Recorder::default());
+        let options = SolveOptions::new().progress(recorder.clone(), 10);
+        assert_eq!(Solver::new(field.clone(), info.clone()).solve_with(&options), SolveResult::NoSolution);
+        check(&recorder);
+
+        let recorder = Arc::new(Recorder::default());
+        let options = SolveOptions::new().progress(recorder.clone(), 10);
+        let mut solver = NoneRecursiveSolver::new(field.clone(), info.clone());
+        assert_eq!(solver.solve_with(&options), SolveResult::NoSolution);
+        check(&recorder);
+
+        let recorder = Arc::new(Recorder::default());
+        let options = SolveOptions::new().progress(recorder.clone(), 10);
+        assert_eq!(solve_parallel(field, info, 4, &options, &[]), SolveResult::NoSolution);
+        check(&recorder);
+    }
+}
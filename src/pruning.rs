@@ -0,0 +1,123 @@
+use std::fmt::Debug;
+
+use crate::field::Field;
+use crate::nazopuyo_info::NazopuyoInfo;
+
+/// 探索の枝刈り
+/// 解につながる盤面を切ってはいけない (許容的であること)
+pub trait Pruner<F: Field>: Debug + Send + Sync {
+    /// depth 手目を置く前の盤面から、もう条件を満たせないなら true
+    fn prune(&self, field: &F, info: &NazopuyoInfo, depth: u32) -> bool;
+}
+
+/// 盤面と残りのツモから見積もった上限
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Estimate {
+    /// 色ごとのぷよの数 (盤面 + 残りのツモ, 添字は色番号)
+    pub counts: [u32; 7],
+    /// 色ごとに消せるぷよの数の上限
+    /// pop_count 個に満たない色は 1 個も消せない。足りていれば全部消せるかもしれない
+    pub max_clearable: [u32; 7],
+    /// 連鎖数の上限
+    pub max_chain: u32,
+}
+
+/// 連鎖は 1 回ごとに最低 1 グループ (同じ色 pop_count 個) 消えるので、
+/// 色ごとに作れるグループ数の合計が連鎖数の上限になる
+/// 13 段目のぷよも落ちてくれば使えるので数える
+pub fn estimate<F: Field>(field: &F, info: &NazopuyoInfo, depth: u32) -> Estimate {
+    let mut counts = [0u32; 7];
+    for i in 0..F::HEIGHT {
+        for j in 0..F::WIDTH {
+            let color = field.get(i, j).code() as usize;
+            if color < counts.len() {
+                counts[color] += 1;
+            }
+        }
+    }
+    for pair in info.next.pairs()[depth as usize..].iter() {
+        for &color in pair.iter() {
+            if (color as usize) < counts.len() {
+                counts[color as usize] += 1;
+            }
+        }
+    }
+
+    let pop_count = info.rules.pop_count.max(1) as u32;
+    let mut max_clearable = [0u32; 7];
+    for color in 2..7 {
+        if counts[color] >= pop_count {
+            max_clearable[color] = counts[color];
+        }
+    }
+    let max_chain = max_clearable.iter().map(|c| c / pop_count).sum();
+
+    Estimate {
+        counts,
+        max_clearable,
+        max_chain,
+    }
+}
+
+/// 連鎖数の上限が目標に届かなければ切る
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChainUpperBound;
+
+impl<F: Field> Pruner<F> for ChainUpperBound {
+    fn prune(&self, field: &F, info: &NazopuyoInfo, depth: u32) -> bool {
+        estimate(field, info, depth).max_chain < info.chain
+    }
+}
+
+pub(crate) fn any_prune<F: Field>(
+    pruners: &[Box<dyn Pruner<F>>],
+    field: &F,
+    info: &NazopuyoInfo,
+    depth: u32,
+) -> bool {
+    pruners.iter().any(|p| p.prune(field, info, depth))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cell::Cell;
+    use crate::naive_field::NaiveField;
+    use crate::naive_next_puyo::NaiveNextPuyo;
+    use crate::rules::Rules;
+
+    #[test]
+    fn estimate_counts_board_and_rest_of_next() {
+        let mut field = NaiveField::new();
+        field.set(0, 0, Cell::RED);
+        field.set(12, 0, Cell::RED);
+        field.set(12, 1, Cell::RED);
+        field.set(12, 2, Cell::Ojama);
+        field.set(12, 3, Cell::BLUE);
+        let next = NaiveNextPuyo::from_vec(vec![[3, 3], [2, 3], [2, 4]]).unwrap();
+        let info = NazopuyoInfo::new(2, next);
+
+        let e = estimate(&field, &info, 0);
+        assert_eq!(e.counts[1], 1);
+        assert_eq!(e.counts[2], 5);
+        assert_eq!(e.counts[3], 4);
+        // 緑 1 個は消せない
+        assert_eq!(e.counts[4], 1);
+        assert_eq!(e.max_clearable, [0, 0, 5, 4, 0, 0, 0]);
+        assert_eq!(e.max_chain, 2);
+        assert!(!ChainUpperBound.prune(&field, &info, 0));
+
+        // 1 手目のツモを使わなかったことにすると青が 3 個しかない
+        let e = estimate(&field, &info, 1);
+        assert_eq!(e.counts[3], 2);
+        assert_eq!(e.max_clearable, [0, 0, 5, 0, 0, 0, 0]);
+        assert_eq!(e.max_chain, 1);
+        assert!(ChainUpperBound.prune(&field, &info, 1));
+
+        // 2 個で消えるなら残りの青 2 個も消せる
+        let info = info.rules(Rules::TSU.pop_count(2));
+        let e = estimate(&field, &info, 1);
+        assert_eq!(e.max_clearable, [0, 0, 5, 2, 0, 0, 0]);
+        assert_eq!(e.max_chain, 2 + 1);
+    }
+}
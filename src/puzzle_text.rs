@@ -0,0 +1,307 @@
+//! 人が読み書きするためのテキスト形式
+//!
+//! ```text
+//! # 6 手で 12 連鎖
+//! ......
+//! ......
+//! ......
+//! ......
+//! .pprr.
+//! .pbbr.
+//! .rbgg.
+//! .rrgy.
+//! .bbyy.
+//! .gbrr.
+//! .ggyr.
+//! .ryyb.
+//! .rrbb.
+//! next: rb rg yb pg ry rb
+//! chain: 12
+//! ```
+//!
+//! - 盤面は見た目どおり上から下へ 1 行 6 文字。13 行なら 1 行目が 13 段目 (見えない段)、12 行なら 13 段目は空
+//!   (6 列 13 段でない盤面では 1 行 WIDTH 文字、HEIGHT 行か HEIGHT - 1 行)
+//! - `.` が空白、`@` おじゃま、`r` `b` `g` `y` `p` が色ぷよ、`h` が固ぷよ
+//! - `next:` の 1 組目の文字が軸ぷよ (`NaiveNextPuyo::pair(i)[0]`)
+//! - `#` から始まる行と空行は読み飛ばす
+
+use std::fmt;
+
+use crate::cell::Cell;
+use crate::field::Field;
+use crate::naive_next_puyo::NaiveNextPuyo;
+use crate::nazopuyo_info::NazopuyoInfo;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// 盤面に使えない文字
+    UnknownCell(char),
+    /// 盤面の行が盤面の幅 (ふつうは 6) 文字でない
+    RowWidth { found: usize, expected: usize },
+    /// 盤面の行が max 行より多い
+    TooManyRows { max: usize },
+    /// 盤面の行が min 行未満
+    TooFewRows { found: usize, min: usize },
+    /// 知らないキー
+    UnknownKey(String),
+    /// 同じキーが二回出てきた
+    DuplicateKey(String),
+    /// ネクストの組が 2 文字でない、または色ぷよでない
+    BadPair(String),
+    /// ネクストが 10 組より多い
+    TooManyPairs(usize),
+    /// 連鎖数が数字でない
+    BadChain(String),
+    MissingNext,
+    MissingChain,
+}
+
+/// どこで何がおかしかったか
+/// line, column は 1 始まり (ファイル全体に関わるものは column 0)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseErrorKind::UnknownCell(c) => write!(f, "unknown cell {:?}", c),
+            ParseErrorKind::RowWidth { found, expected } => {
+                write!(f, "row has {} cells, expected {}", found, expected)
+            }
+            ParseErrorKind::TooManyRows { max } => write!(f, "too many rows, expected at most {}", max),
+            ParseErrorKind::TooFewRows { found, min } => {
+                write!(f, "field has {} rows, expected {} or {}", found, min, min + 1)
+            }
+            ParseErrorKind::UnknownKey(k) => write!(f, "unknown key {:?}", k),
+            ParseErrorKind::DuplicateKey(k) => write!(f, "duplicate key {:?}", k),
+            ParseErrorKind::BadPair(p) => write!(f, "bad pair {:?}", p),
+            ParseErrorKind::TooManyPairs(n) => write!(f, "{} pairs, expected at most 10", n),
+            ParseErrorKind::BadChain(c) => write!(f, "bad chain count {:?}", c),
+            ParseErrorKind::MissingNext => write!(f, "missing \"next:\" line"),
+            ParseErrorKind::MissingChain => write!(f, "missing \"chain:\" line"),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.column == 0 {
+            write!(f, "line {}: {}", self.line, self.kind)
+        } else {
+            write!(f, "line {}, column {}: {}", self.line, self.column, self.kind)
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub(crate) fn error(line: usize, column: usize, kind: ParseErrorKind) -> ParseError {
+    ParseError { line, column, kind }
+}
+
+pub fn parse_puzzle<F: Field>(text: &str) -> Result<(F, NazopuyoInfo), ParseError> {
+    let mut rows: Vec<Vec<Cell>> = vec![];
+    let mut next: Option<NaiveNextPuyo> = None;
+    let mut chain: Option<u32> = None;
+    let mut last_line = 0;
+
+    for (n, line) in text.lines().enumerate() {
+        let line_no = n + 1;
+        last_line = line_no;
+        let trimmed = line.trim_end();
+        if trimmed.trim_start().is_empty() || trimmed.trim_start().starts_with('#') {
+            continue;
+        }
+
+        if let Some(colon) = trimmed.find(':') {
+            let key = trimmed[..colon].trim();
+            let value = &trimmed[colon + 1..];
+            // 値の前までの文字数。列はバイトでなく文字で数える
+            let value_start = trimmed[..colon].chars().count() + 1;
+            match key {
+                "next" => {
+                    if next.is_some() {
+                        return Err(error(line_no, 1, ParseErrorKind::DuplicateKey(key.to_string())));
+                    }
+                    next = Some(parse_next(value, line_no, value_start)?);
+                }
+                "chain" => {
+                    if chain.is_some() {
+                        return Err(error(line_no, 1, ParseErrorKind::DuplicateKey(key.to_string())));
+                    }
+                    let column = value_start + value.chars().take_while(|c| c.is_whitespace()).count() + 1;
+                    let v = value.trim();
+                    chain = Some(
+                        v.parse()
+                            .map_err(|_| error(line_no, column, ParseErrorKind::BadChain(v.to_string())))?,
+                    );
+                }
+                _ => {
+                    return Err(error(line_no, 1, ParseErrorKind::UnknownKey(key.to_string())));
+                }
+            }
+            continue;
+        }
+
+        if rows.len() == F::HEIGHT {
+            return Err(error(line_no, 1, ParseErrorKind::TooManyRows { max: F::HEIGHT }));
+        }
+        rows.push(parse_row::<F>(trimmed, line_no)?);
+    }
+
+    if rows.len() < F::HEIGHT - 1 {
+        let kind = ParseErrorKind::TooFewRows {
+            found: rows.len(),
+            min: F::HEIGHT - 1,
+        };
+        return Err(error(last_line, 0, kind));
+    }
+    let next = next.ok_or_else(|| error(last_line, 0, ParseErrorKind::MissingNext))?;
+    let chain = chain.ok_or_else(|| error(last_line, 0, ParseErrorKind::MissingChain))?;
+
+    // 12 行なら 13 段目は空
+    Ok((rows_to_field(&rows), NazopuyoInfo::new(chain, next)))
+}
+
+/// 盤面の 1 行 (WIDTH 文字)
+pub(crate) fn parse_row<F: Field>(line: &str, line_no: usize) -> Result<Vec<Cell>, ParseError> {
+    let mut row = vec![];
+    for (k, c) in line.chars().enumerate() {
+        let cell = Cell::from_char(c).ok_or_else(|| error(line_no, k + 1, ParseErrorKind::UnknownCell(c)))?;
+        row.push(cell);
+    }
+    if row.len() != F::WIDTH {
+        let kind = ParseErrorKind::RowWidth {
+            found: row.len(),
+            expected: F::WIDTH,
+        };
+        return Err(error(line_no, 1, kind));
+    }
+    Ok(row)
+}
+
+/// 行を下に詰めて盤面にする (HEIGHT 行以下)
+pub(crate) fn rows_to_field<F: Field>(rows: &[Vec<Cell>]) -> F {
+    let offset = F::HEIGHT - rows.len();
+    let mut field = F::new();
+    for (i, row) in rows.iter().enumerate() {
+        for (j, &cell) in row.iter().enumerate() {
+            field.set(i + offset, j, cell);
+        }
+    }
+    field
+}
+
+fn parse_next(value: &str, line: usize, value_start: usize) -> Result<NaiveNextPuyo, ParseError> {
+    let mut pairs = vec![];
+    for word in value.split_whitespace() {
+        // word は value の一部なので、その手前の文字数から列がわかる
+        let offset = word.as_ptr() as usize - value.as_ptr() as usize;
+        let column = value_start + value[..offset].chars().count() + 1;
+        let colors: Vec<_> = word.chars().map(|c| Cell::from_char(c).and_then(Cell::color)).collect();
+        match colors[..] {
+            [Some(a), Some(b)] => pairs.push([a, b]),
+            _ => return Err(error(line, column, ParseErrorKind::BadPair(word.to_string()))),
+        }
+    }
+    if pairs.len() > 10 {
+        return Err(error(line, 0, ParseErrorKind::TooManyPairs(pairs.len())));
+    }
+    Ok(NaiveNextPuyo::from_colors(pairs))
+}
+
+/// 見えない段も含めた HEIGHT 行 (ふつうは 13 行) の盤面で書き出す
+pub fn write_puzzle<F: Field>(field: &F, info: &NazopuyoInfo) -> String {
+    let mut res = String::new();
+    for i in 0..F::HEIGHT {
+        for j in 0..F::WIDTH {
+            res.push(field.get(i, j).to_char());
+        }
+        res.push('\n');
+    }
+    res.push_str("next:");
+    for i in 0..info.next.len() {
+        let pair = info.next.pair(i);
+        res.push(' ');
+        res.push(pair[0].to_char());
+        res.push(pair[1].to_char());
+    }
+    res.push('\n');
+    res.push_str(&format!("chain: {}\n", info.chain));
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::field1d::Field1D;
+    use crate::naive_field::{NaiveField, NaiveFieldOf};
+
+    #[test]
+    fn parse_and_write() {
+        let text = include_str!("../puzzles/chain_6depth.txt");
+        let (field, info) = parse_puzzle::<NaiveField>(text).unwrap();
+        assert_eq!(field.get(4, 1), Cell::PURPLE);
+        assert_eq!(field.get(12, 4), Cell::BLUE);
+        assert_eq!(field.get(12, 0), Cell::Empty);
+        assert_eq!(info.chain, 12);
+        assert_eq!(info.next.len(), 6);
+        assert_eq!(info.next.pairs()[3], [6, 4]);
+
+        let written = write_puzzle(&field, &info);
+        let body: Vec<&str> = text.lines().filter(|l| !l.starts_with('#')).collect();
+        assert_eq!(written.lines().collect::<Vec<_>>(), body);
+    }
+
+    #[test]
+    fn twelve_rows() {
+        let mut text = String::new();
+        for _ in 0..11 {
+            text.push_str("......\n");
+        }
+        text.push_str("r.....\nnext: rr\nchain: 1\n");
+        let (field, info) = parse_puzzle::<Field1D>(&text).unwrap();
+        assert_eq!(field.get(12, 0), Cell::RED);
+        assert_eq!(info.next.pairs()[0], [2, 2]);
+    }
+
+    #[test]
+    fn errors() {
+        let rows = "......\n".repeat(12);
+        let cases = [
+            (format!("{}..x...\nnext: rb\nchain: 1\n", rows), 13, 3, ParseErrorKind::UnknownCell('x')),
+            (format!("{}.....\nnext: rb\nchain: 1\n", rows), 13, 1, ParseErrorKind::RowWidth { found: 5, expected: 6 }),
+            (format!("{}next: rb r@\nchain: 1\n", rows), 13, 10, ParseErrorKind::BadPair("r@".to_string())),
+            (format!("{}next: rb\nchain: x\n", rows), 14, 8, ParseErrorKind::BadChain("x".to_string())),
+            // タブや続いた空白で区切ってもよい。列はバイトでなく文字で数える
+            (format!("{}next:\trb  r@\nchain: 1\n", rows), 13, 11, ParseErrorKind::BadPair("r@".to_string())),
+            (format!("{}next: rb\u{3000}ré\nchain: 1\n", rows), 13, 10, ParseErrorKind::BadPair("ré".to_string())),
+            (format!("{}next: rb\nchain:\u{3000}x\n", rows), 14, 8, ParseErrorKind::BadChain("x".to_string())),
+            (format!("{}next: rb\ncolor: 1\n", rows), 14, 1, ParseErrorKind::UnknownKey("color".to_string())),
+            (format!("{}chain: 1\n", rows), 13, 0, ParseErrorKind::MissingNext),
+            ("......\nnext: rb\nchain: 1\n".to_string(), 3, 0, ParseErrorKind::TooFewRows { found: 1, min: 12 }),
+        ];
+        for (text, line, column, kind) in cases {
+            let err = parse_puzzle::<NaiveField>(&text).unwrap_err();
+            assert_eq!(err, ParseError { line, column, kind });
+        }
+        let err = parse_puzzle::<NaiveField>(&format!("{}..x...\n", rows)).unwrap_err();
+        assert_eq!(err.to_string(), "line 13, column 3: unknown cell 'x'");
+        let err = parse_puzzle::<NaiveField>(&format!("{}.....\n", rows)).unwrap_err();
+        assert_eq!(err.to_string(), "line 13, column 1: row has 5 cells, expected 6");
+    }
+
+    #[test]
+    fn wide_field() {
+        let text = format!("{}r.......\nnext: rr\nchain: 1\n", "........\n".repeat(13));
+        let (field, info) = parse_puzzle::<NaiveFieldOf<8, 14>>(&text).unwrap();
+        assert_eq!(field.get(13, 0), Cell::RED);
+        assert_eq!(write_puzzle(&field, &info), text);
+        let err = parse_puzzle::<NaiveField>(&text).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::RowWidth { found: 8, expected: 6 });
+    }
+}
@@ -0,0 +1,220 @@
+//! 端末に盤面を描く
+//!
+//! どの Field でも同じ見た目で描ける。右側にネクストを並べたり、置いたばかりのぷよを目立たせたりできる
+//!
+//! - `Style::Ansi` 色付きの ● (colored を使う)
+//! - `Style::Ascii` puzzle_text と同じ文字。目立たせるマスは大文字 (おじゃまは #、空白は _)
+//! - `Style::Unicode` 色の丸の絵文字。目立たせるぷよは四角
+
+use colored::*;
+
+use crate::cell::{Cell, Color};
+use crate::field::Field;
+use crate::naive_next_puyo::NaiveNextPuyo;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Ansi,
+    Ascii,
+    Unicode,
+}
+
+impl Style {
+    /// 名前 (ansi, ascii, unicode) から
+    pub fn from_name(name: &str) -> Option<Style> {
+        match name {
+            "ansi" => Some(Style::Ansi),
+            "ascii" => Some(Style::Ascii),
+            "unicode" => Some(Style::Unicode),
+            _ => None,
+        }
+    }
+
+    /// 1 マスが端末で何桁になるか (絵文字は 2 桁)
+    pub fn width(self) -> usize {
+        match self {
+            Style::Ansi | Style::Ascii => 1,
+            Style::Unicode => 2,
+        }
+    }
+
+    /// 1 マス分の文字列
+    pub fn cell(self, cell: Cell, highlight: bool) -> String {
+        match self {
+            Style::Ascii => {
+                let c = match (cell, highlight) {
+                    (_, false) => cell.to_char(),
+                    (Cell::Empty, true) => '_',
+                    (Cell::Ojama, true) => '#',
+                    (_, true) => cell.to_char().to_ascii_uppercase(),
+                };
+                c.to_string()
+            }
+            Style::Unicode => match (cell, highlight) {
+                (Cell::Empty, _) => "・".to_string(),
+                (Cell::Ojama, _) => "⚪".to_string(),
+                (Cell::Hard, _) => "⬜".to_string(),
+                (Cell::Color(color), false) => ["🔴", "🔵", "🟢", "🟡", "🟣"][color as usize].to_string(),
+                (Cell::Color(color), true) => ["🟥", "🟦", "🟩", "🟨", "🟪"][color as usize].to_string(),
+            },
+            Style::Ansi => {
+                let s = match cell {
+                    Cell::Empty => ".".dimmed(),
+                    Cell::Ojama => "●".white(),
+                    Cell::Hard => "■".bright_black(),
+                    Cell::Color(color) => "●".color(ansi_color(color)),
+                };
+                if highlight {
+                    s.reversed().to_string()
+                } else {
+                    s.to_string()
+                }
+            }
+        }
+    }
+}
+
+fn ansi_color(color: Color) -> colored::Color {
+    match color {
+        Color::Red => colored::Color::Red,
+        Color::Blue => colored::Color::Blue,
+        Color::Green => colored::Color::Green,
+        Color::Yellow => colored::Color::Yellow,
+        Color::Purple => colored::Color::Magenta,
+    }
+}
+
+/// 盤面の描き方
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Renderer {
+    style: Style,
+    /// 右に並べるネクスト ([軸ぷよ, 子ぷよ])
+    next: Vec<[Cell; 2]>,
+    /// 目立たせるマス (段, 列)
+    highlight: Vec<(usize, usize)>,
+}
+
+impl Renderer {
+    pub fn new(style: Style) -> Self {
+        Self {
+            style,
+            next: vec![],
+            highlight: vec![],
+        }
+    }
+
+    /// from 組目 (0 始まり) から後ろのネクストを右に並べる
+    pub fn next(mut self, next: &NaiveNextPuyo, from: usize) -> Self {
+        self.next = (from..next.len()).map(|i| next.pair(i)).collect();
+        self
+    }
+
+    pub fn highlight(mut self, cells: impl IntoIterator<Item = (usize, usize)>) -> Self {
+        self.highlight = cells.into_iter().collect();
+        self
+    }
+
+    /// 見えない段も含めて上から 1 段 1 行で描く。ネクストは 1 行に 1 組 (軸ぷよ, 子ぷよ の順)
+    pub fn render<F: Field>(&self, field: &F) -> String {
+        let mut res = String::new();
+        for y in 0..F::HEIGHT.max(self.next.len()) {
+            if y < F::HEIGHT {
+                for x in 0..F::WIDTH {
+                    let highlight = self.highlight.contains(&(y, x));
+                    res.push_str(&self.style.cell(field.get(y, x), highlight));
+                }
+            } else {
+                res.push_str(&" ".repeat(F::WIDTH * self.style.width()));
+            }
+            if let Some(pair) = self.next.get(y) {
+                res.push_str("  ");
+                res.push_str(&self.style.cell(pair[0], false));
+                res.push_str(&self.style.cell(pair[1], false));
+            }
+            res.push('\n');
+        }
+        res
+    }
+}
+
+/// before から after で空白からぷよになったマス (置いたばかりのぷよ)
+pub fn placed_cells<F: Field>(before: &F, after: &F) -> Vec<(usize, usize)> {
+    let mut res = vec![];
+    for y in 0..F::HEIGHT {
+        for x in 0..F::WIDTH {
+            if before.get(y, x).is_empty() && !after.get(y, x).is_empty() {
+                res.push((y, x));
+            }
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::field1d::Field1D;
+    use crate::field_naive_bit::FieldNaiveBit;
+    use crate::naive_field::{NaiveField, NaiveFieldOf};
+
+    fn field<F: Field>() -> F {
+        let mut field = F::new();
+        field.set(12, 0, Cell::RED);
+        field.set(12, 1, Cell::Ojama);
+        field.set(11, 0, Cell::PURPLE);
+        field
+    }
+
+    #[test]
+    fn ascii() {
+        let next = NaiveNextPuyo::from_vec(vec![[2, 3], [4, 5]]).unwrap();
+        let before = NaiveField::new();
+        let after: NaiveField = field();
+        let text = Renderer::new(Style::Ascii)
+            .next(&next, 1)
+            .highlight(placed_cells(&before, &after).into_iter().filter(|&(y, _)| y == 11))
+            .render(&after);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 13);
+        assert_eq!(lines[0], "......  gy");
+        assert_eq!(lines[11], "P.....");
+        assert_eq!(lines[12], "r@....");
+
+        // 大文字の無いマスも目立たせられる
+        let mut hard = NaiveField::new();
+        hard.set(12, 0, Cell::Hard);
+        hard.set(12, 1, Cell::Ojama);
+        let text = Renderer::new(Style::Ascii).highlight([(12, 0), (12, 1), (12, 2)]).render(&hard);
+        assert_eq!(text.lines().nth(12), Some("H#_..."));
+
+        // どの Field でも同じ
+        let renderer = Renderer::new(Style::Ascii);
+        assert_eq!(renderer.render(&field::<Field1D>()), renderer.render(&after));
+        assert_eq!(renderer.render(&field::<FieldNaiveBit>()), renderer.render(&after));
+        // Display も同じ見た目
+        assert_eq!(after.to_string(), renderer.render(&after));
+        assert_eq!(field::<Field1D>().to_string(), renderer.render(&after));
+        assert_eq!(field::<FieldNaiveBit>().to_string(), renderer.render(&after));
+    }
+
+    #[test]
+    fn unicode_and_ansi() {
+        let field: NaiveField = field();
+        let text = Renderer::new(Style::Unicode).highlight([(12, 0)]).render(&field);
+        assert_eq!(text.lines().nth(12), Some("🟥⚪・・・・"));
+        assert_eq!(text.lines().nth(11), Some("🟣・・・・・"));
+        // 色が付くかは環境しだいなので、形だけ見る
+        let text = Renderer::new(Style::Ansi).render(&field);
+        assert_eq!(text.lines().count(), 13);
+        assert!(text.lines().nth(12).unwrap().contains('●'));
+        // ネクストが盤面より長ければ下に足す
+        let long = NaiveNextPuyo::from_vec(vec![[2, 2]; 10]).unwrap();
+        let text = Renderer::new(Style::Ascii).next(&long, 0).render(&NaiveFieldOf::<6, 8>::new());
+        assert_eq!(text.lines().count(), 10);
+        assert_eq!(text.lines().last(), Some("        rr"));
+        // 絵文字は 2 桁なので盤面の下も 2 桁ずつ空ける
+        let text = Renderer::new(Style::Unicode).next(&long, 0).render(&NaiveFieldOf::<6, 8>::new());
+        assert_eq!(text.lines().nth(7), Some("・・・・・・  🔴🔴"));
+        assert_eq!(text.lines().last(), Some("              🔴🔴"));
+    }
+}
@@ -0,0 +1,209 @@
+//! ルール (何個で消えるか、見えない段、窒息点など)
+//!
+//! 既定はぷよぷよ通。連鎖や窒息の判定は `field::chain_with` などでこれを見る
+//! 初代ぷよぷよ (アーケード) のルールは `Rules::CLASSIC`、フィーバーは `Rules::FEVER`
+//!
+//! 扱っていないもの: 太陽ぷよ (ぷよぷよ SUN)。消えると送るおじゃまが増えるぷよだが、
+//! Cell の番号 0-7 は全部使っていて、FieldNaiveBit の 1 マス 3 bit に入らない。
+//! 入れるなら Cell に番号を足して 1 マス 4 bit にする (高さの上限が 21 段から 16 段になる)
+
+/// 消えたぷよの隣のおじゃまの扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum OjamaClear {
+    /// 隣で消えると消える (固ぷよはおじゃまになる)
+    Adjacent,
+    /// 消えない
+    Never,
+}
+
+/// 見えない段に置いたぷよの扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum HiddenRow {
+    /// 残る (通の 13 段目)
+    Keep,
+    /// 消える
+    Vanish,
+}
+
+/// 点数の計算に使う表
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum PowerTable {
+    Tsu,
+    Classic,
+    /// フィーバールールの通常時
+    Fever,
+}
+
+const TSU_CHAIN_BONUS: [u32; 19] = [
+    0, 8, 16, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448, 480, 512,
+];
+const CLASSIC_CHAIN_BONUS: [u32; 9] = [0, 8, 16, 32, 64, 128, 256, 512, 999];
+const FEVER_CHAIN_BONUS: [u32; 15] = [4, 12, 24, 32, 48, 96, 160, 240, 320, 480, 600, 700, 800, 900, 999];
+const COLOR_BONUS: [u32; 5] = [0, 3, 6, 12, 24];
+const FEVER_COLOR_BONUS: [u32; 5] = [0, 2, 4, 8, 16];
+
+impl PowerTable {
+    /// chain 連鎖目 (1 始まり) の連鎖ボーナス。表より長い連鎖は最後の値
+    pub fn chain_bonus(self, chain: u32) -> u32 {
+        let table: &[u32] = match self {
+            PowerTable::Tsu => &TSU_CHAIN_BONUS,
+            PowerTable::Classic => &CLASSIC_CHAIN_BONUS,
+            PowerTable::Fever => &FEVER_CHAIN_BONUS,
+        };
+        let i = (chain.max(1) as usize - 1).min(table.len() - 1);
+        table[i]
+    }
+
+    /// 同時に消えた色の数による色数ボーナス
+    pub fn color_bonus(self, colors: usize) -> u32 {
+        let table = match self {
+            PowerTable::Tsu | PowerTable::Classic => &COLOR_BONUS,
+            PowerTable::Fever => &FEVER_COLOR_BONUS,
+        };
+        table[colors.clamp(1, table.len()) - 1]
+    }
+
+    /// 1 グループの個数による連結ボーナス
+    pub fn group_bonus(self, size: usize) -> u32 {
+        match (self, size) {
+            (_, 0..=4) => 0,
+            (PowerTable::Fever, 5..=10) => size as u32 - 4,
+            (PowerTable::Fever, _) => 8,
+            (_, 5..=10) => size as u32 - 3,
+            _ => 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Rules {
+    /// 同じ色が何個つながると消えるか
+    pub pop_count: usize,
+    /// 上から何段が見えない段か。見えない段のぷよはつながらず、消えず、巻き込まれない
+    pub hidden_rows: usize,
+    /// ここ (段, 列) が埋まると窒息
+    pub death_cell: (usize, usize),
+    pub ojama_clear: OjamaClear,
+    /// 全消ししたときに次の連鎖に足される点
+    pub all_clear_bonus: u32,
+    /// おじゃまぷよ 1 個分の点
+    pub target_point: u32,
+    pub power: PowerTable,
+    pub hidden_row: HiddenRow,
+    /// 両隣が埋まっていても 2 回転で上下を入れ替えられるか
+    pub quick_turn: bool,
+}
+
+impl Rules {
+    /// ぷよぷよ通
+    pub const TSU: Rules = Rules {
+        pop_count: 4,
+        hidden_rows: 1,
+        death_cell: (1, 2),
+        ojama_clear: OjamaClear::Adjacent,
+        all_clear_bonus: 2100,
+        target_point: 70,
+        power: PowerTable::Tsu,
+        hidden_row: HiddenRow::Keep,
+        quick_turn: true,
+    };
+
+    /// 初代ぷよぷよ (アーケード)
+    /// 連鎖ボーナスが倍々に増え、見えない段に置いたぷよは消え、クイックターンが無い
+    pub const CLASSIC: Rules = Rules {
+        pop_count: 4,
+        hidden_rows: 1,
+        death_cell: (1, 2),
+        ojama_clear: OjamaClear::Adjacent,
+        all_clear_bonus: 8500,
+        target_point: 120,
+        power: PowerTable::Classic,
+        hidden_row: HiddenRow::Vanish,
+        quick_turn: false,
+    };
+
+    /// ぷよぷよフィーバー (通常時)
+    /// 全消しの点は無く、代わりに連鎖のタネが降ってくる (`fever` を参照)
+    /// 相殺は `score::offset`。太陽ぷよは無い (モジュールの説明を参照)
+    pub const FEVER: Rules = Rules {
+        pop_count: 4,
+        hidden_rows: 1,
+        death_cell: (1, 2),
+        ojama_clear: OjamaClear::Adjacent,
+        all_clear_bonus: 0,
+        target_point: 120,
+        power: PowerTable::Fever,
+        hidden_row: HiddenRow::Keep,
+        quick_turn: true,
+    };
+
+    /// 名前 (tsu, classic, fever) から
+    pub fn from_name(name: &str) -> Option<Rules> {
+        match name {
+            "tsu" => Some(Rules::TSU),
+            "classic" => Some(Rules::CLASSIC),
+            "fever" => Some(Rules::FEVER),
+            _ => None,
+        }
+    }
+
+    /// n 個で消える
+    pub fn pop_count(mut self, n: usize) -> Self {
+        self.pop_count = n;
+        self
+    }
+
+    pub fn hidden_rows(mut self, rows: usize) -> Self {
+        self.hidden_rows = rows;
+        self
+    }
+
+    pub fn death_cell(mut self, y: usize, x: usize) -> Self {
+        self.death_cell = (y, x);
+        self
+    }
+
+    pub fn ojama_clear(mut self, ojama_clear: OjamaClear) -> Self {
+        self.ojama_clear = ojama_clear;
+        self
+    }
+
+    pub fn all_clear_bonus(mut self, bonus: u32) -> Self {
+        self.all_clear_bonus = bonus;
+        self
+    }
+
+    pub fn target_point(mut self, point: u32) -> Self {
+        self.target_point = point;
+        self
+    }
+
+    pub fn power(mut self, power: PowerTable) -> Self {
+        self.power = power;
+        self
+    }
+
+    pub fn hidden_row(mut self, hidden_row: HiddenRow) -> Self {
+        self.hidden_row = hidden_row;
+        self
+    }
+
+    pub fn quick_turn(mut self, quick_turn: bool) -> Self {
+        self.quick_turn = quick_turn;
+        self
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules::TSU
+    }
+}
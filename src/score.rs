@@ -0,0 +1,115 @@
+//! 連鎖の点数
+//!
+//! 1 回の消去ごとに 10 × 消えた個数 × (連鎖ボーナス + 色数ボーナス + 連結ボーナス) を足す
+//! ボーナスの合計は 1 から 999 の間に収める。表は `Rules::power` で選ぶ
+
+use std::collections::HashSet;
+
+use crate::field::{pop_groups, Field};
+use crate::rules::Rules;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChainScore {
+    pub chain: u32,
+    pub score: u32,
+    /// 連鎖のあと盤面が空になった
+    pub all_clear: bool,
+}
+
+impl ChainScore {
+    /// 相手に送るおじゃまぷよの数 (端数は切り捨て)
+    pub fn ojama(&self, rules: &Rules) -> u32 {
+        self.score / rules.target_point.max(1)
+    }
+}
+
+/// 相殺。送るおじゃまと降ってくる予定のおじゃまを打ち消し合って、残った (送る数, 降る数) を返す
+pub fn offset(sent: u32, incoming: u32) -> (u32, u32) {
+    let n = sent.min(incoming);
+    (sent - n, incoming - n)
+}
+
+/// rules で連鎖させて点数を数える
+/// after_all_clear が true なら (前の連鎖で全消ししていれば) 全消しボーナスを足す
+pub fn score_chain<F: Field + Clone>(field: &mut F, rules: &Rules, after_all_clear: bool) -> ChainScore {
+    let mut res = ChainScore::default();
+    while let Some((next_field, groups)) = pop_groups(field, rules) {
+        res.chain += 1;
+        let popped: usize = groups.iter().map(|g| g.cells.len()).sum();
+        let colors = groups.iter().map(|g| g.color).collect::<HashSet<_>>().len();
+        let bonus = rules.power.chain_bonus(res.chain)
+            + rules.power.color_bonus(colors)
+            + groups.iter().map(|g| rules.power.group_bonus(g.cells.len())).sum::<u32>();
+        res.score += 10 * popped as u32 * bonus.clamp(1, 999);
+        *field = next_field;
+        field.fall();
+    }
+    if res.chain > 0 {
+        res.all_clear = field.is_empty();
+        if after_all_clear {
+            res.score += rules.all_clear_bonus;
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cell::Cell;
+    use crate::naive_field::NaiveField;
+    use crate::rules::PowerTable;
+
+    #[test]
+    fn two_chain() {
+        // 赤 4 個が消えて、上の青が落ちて青 4 個が消える
+        let mut field = NaiveField::new();
+        for (y, x) in [(12, 0), (11, 0), (10, 0), (12, 1)] {
+            field.set(y, x, Cell::RED);
+        }
+        for (y, x) in [(9, 0), (11, 1), (12, 2), (11, 2)] {
+            field.set(y, x, Cell::BLUE);
+        }
+        let res = score_chain(&mut field.clone(), &Rules::TSU, false);
+        assert_eq!(res, ChainScore { chain: 2, score: 40 + 320, all_clear: true });
+        let res = score_chain(&mut field.clone(), &Rules::TSU, true);
+        assert_eq!(res.score, 40 + 320 + 2100);
+    }
+
+    #[test]
+    fn bonuses() {
+        // 5 個つながりと 2 色同時
+        let mut field = NaiveField::new();
+        for x in 0..5 {
+            field.set(12, x, Cell::RED);
+            field.set(11, x, Cell::BLUE);
+        }
+        let res = score_chain(&mut field, &Rules::TSU, false);
+        assert_eq!(res, ChainScore { chain: 1, score: 10 * 10 * (3 + 2 + 2), all_clear: true });
+
+        assert_eq!(PowerTable::Tsu.chain_bonus(6), 96);
+        assert_eq!(PowerTable::Classic.chain_bonus(6), 128);
+        assert_eq!(PowerTable::Classic.chain_bonus(19), 999);
+        assert_eq!(PowerTable::Tsu.chain_bonus(30), 512);
+        assert_eq!(PowerTable::Tsu.group_bonus(11), 10);
+        assert_eq!(PowerTable::Fever.chain_bonus(1), 4);
+        assert_eq!(PowerTable::Fever.color_bonus(2), 2);
+        assert_eq!(PowerTable::Fever.group_bonus(11), 8);
+    }
+
+    #[test]
+    fn fever_ojama() {
+        // フィーバーは 1 連鎖目から連鎖ボーナスがある
+        let mut field = NaiveField::new();
+        for x in 0..4 {
+            field.set(12, x, Cell::RED);
+        }
+        let res = score_chain(&mut field, &Rules::FEVER, false);
+        assert_eq!(res.score, 10 * 4 * 4);
+        assert_eq!(res.ojama(&Rules::FEVER), 1);
+        assert_eq!(res.ojama(&Rules::TSU), 2);
+        assert_eq!(offset(5, 3), (2, 0));
+        assert_eq!(offset(2, 7), (0, 5));
+    }
+}
@@ -0,0 +1,246 @@
+//! serde 対応 (`serde` feature)
+//!
+//! 盤面の持ち方に依らず同じ形になるようにしてある
+//! - 盤面: `{"rows": ["......", ...]}` 13 段目から下へ 13 行 (テキスト形式と同じ文字)。読むときは 12 行でもよい
+//!   6 列 13 段でない盤面は 1 行 WIDTH 文字で HEIGHT 行
+//! - ネクスト: `["rb", "gy"]` 1 組目の文字が軸ぷよ
+//! - 問題: `{"chain": 12, "next": [...]}`
+//! - 探索結果: `{"status": "solved", "field": {...}}` / `{"status": "no_solution"}` /
+//!   `{"status": "gave_up", "reason": "timeout"}`
+
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+
+use crate::cell::Cell;
+use crate::field::Field;
+use crate::field1d::Field1DOf;
+use crate::field_naive_bit::FieldNaiveBitOf;
+use crate::naive_field::NaiveFieldOf;
+use crate::naive_next_puyo::NaiveNextPuyo;
+use crate::nazopuyo_info::NazopuyoInfo;
+use crate::rules::Rules;
+use crate::solve_options::{GiveUpReason, SolveResult};
+
+#[derive(Serialize, Deserialize)]
+struct FieldRepr {
+    rows: Vec<String>,
+}
+
+fn field_to_repr<F: Field>(field: &F) -> FieldRepr {
+    FieldRepr {
+        rows: (0..F::HEIGHT)
+            .map(|i| (0..F::WIDTH).map(|j| field.get(i, j).to_char()).collect())
+            .collect(),
+    }
+}
+
+fn field_from_repr<F: Field>(repr: FieldRepr) -> Result<F, String> {
+    if repr.rows.len() + 1 != F::HEIGHT && repr.rows.len() != F::HEIGHT {
+        return Err(format!("expected {} or {} rows, got {}", F::HEIGHT - 1, F::HEIGHT, repr.rows.len()));
+    }
+    let offset = F::HEIGHT - repr.rows.len();
+    let mut field = F::new();
+    for (i, row) in repr.rows.iter().enumerate() {
+        if row.chars().count() != F::WIDTH {
+            let count = row.chars().count();
+            return Err(format!("row {} has {} cells, expected {}", i + 1, count, F::WIDTH));
+        }
+        for (j, c) in row.chars().enumerate() {
+            let cell = Cell::from_char(c).ok_or_else(|| format!("row {}: unknown cell {:?}", i + 1, c))?;
+            field.set(i + offset, j, cell);
+        }
+    }
+    Ok(field)
+}
+
+macro_rules! impl_field_serde {
+    ($($t:ident),*) => {
+        $(
+            impl<const W: usize, const H: usize> Serialize for $t<W, H> {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    field_to_repr(self).serialize(serializer)
+                }
+            }
+
+            impl<'de, const W: usize, const H: usize> Deserialize<'de> for $t<W, H> {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    field_from_repr(FieldRepr::deserialize(deserializer)?).map_err(de::Error::custom)
+                }
+            }
+        )*
+    };
+}
+
+impl_field_serde!(NaiveFieldOf, Field1DOf, FieldNaiveBitOf);
+
+impl Serialize for NaiveNextPuyo {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let pairs: Vec<String> = (0..self.len())
+            .map(|i| self.pair(i).iter().map(|c| c.to_char()).collect())
+            .collect();
+        pairs.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for NaiveNextPuyo {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pairs = Vec::<String>::deserialize(deserializer)?;
+        if pairs.len() > 10 {
+            return Err(de::Error::custom(format!("{} pairs, expected at most 10", pairs.len())));
+        }
+        let mut res = vec![];
+        for p in pairs.iter() {
+            let colors: Vec<_> = p.chars().map(|c| Cell::from_char(c).and_then(Cell::color)).collect();
+            match colors[..] {
+                [Some(a), Some(b)] => res.push([a, b]),
+                _ => return Err(de::Error::custom(format!("bad pair {:?}", p))),
+            }
+        }
+        Ok(NaiveNextPuyo::from_colors(res))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct InfoRepr {
+    chain: u32,
+    next: NaiveNextPuyo,
+    /// ぷよぷよ通なら書かない
+    #[serde(default, skip_serializing_if = "is_tsu")]
+    rules: Rules,
+}
+
+fn is_tsu(rules: &Rules) -> bool {
+    *rules == Rules::TSU
+}
+
+impl Serialize for NazopuyoInfo {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        InfoRepr {
+            chain: self.chain,
+            next: self.next.clone(),
+            rules: self.rules,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for NazopuyoInfo {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = InfoRepr::deserialize(deserializer)?;
+        Ok(NazopuyoInfo::new(repr.chain, repr.next).rules(repr.rules))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    Solved,
+    NoSolution,
+    GaveUp,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound(deserialize = "F: Deserialize<'de>"))]
+struct SolveResultRepr<F> {
+    status: Status,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    field: Option<F>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reason: Option<GiveUpReason>,
+}
+
+impl<F: Serialize + Clone> Serialize for SolveResult<F> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let repr = match self {
+            SolveResult::Solved(f) => SolveResultRepr {
+                status: Status::Solved,
+                field: Some(f.clone()),
+                reason: None,
+            },
+            SolveResult::NoSolution => SolveResultRepr {
+                status: Status::NoSolution,
+                field: None,
+                reason: None,
+            },
+            SolveResult::GaveUp(reason) => SolveResultRepr {
+                status: Status::GaveUp,
+                field: None,
+                reason: Some(*reason),
+            },
+        };
+        repr.serialize(serializer)
+    }
+}
+
+impl<'de, F: Deserialize<'de>> Deserialize<'de> for SolveResult<F> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = SolveResultRepr::<F>::deserialize(deserializer)?;
+        match (repr.status, repr.field, repr.reason) {
+            (Status::Solved, Some(f), _) => Ok(SolveResult::Solved(f)),
+            (Status::Solved, None, _) => Err(de::Error::missing_field("field")),
+            (Status::NoSolution, _, _) => Ok(SolveResult::NoSolution),
+            (Status::GaveUp, _, Some(reason)) => Ok(SolveResult::GaveUp(reason)),
+            (Status::GaveUp, _, None) => Err(de::Error::missing_field("reason")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::field1d::Field1D;
+    use crate::field_naive_bit::FieldNaiveBit;
+    use crate::naive_field::NaiveField;
+    use crate::puzzle_text::parse_puzzle;
+
+    #[test]
+    fn same_json_for_every_field() {
+        let text = include_str!("../puzzles/multi_test2.txt");
+        let (naive, info) = parse_puzzle::<NaiveField>(text).unwrap();
+        let (one_d, _) = parse_puzzle::<Field1D>(text).unwrap();
+        let (bit, _) = parse_puzzle::<FieldNaiveBit>(text).unwrap();
+
+        let json = serde_json::to_string(&naive).unwrap();
+        assert_eq!(serde_json::to_string(&one_d).unwrap(), json);
+        assert_eq!(serde_json::to_string(&bit).unwrap(), json);
+        assert!(json.starts_with(r#"{"rows":["......","......","......","...y..""#));
+
+        let back: FieldNaiveBit = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, bit);
+
+        let info_json = serde_json::to_string(&info).unwrap();
+        assert_eq!(info_json, r#"{"chain":8,"next":["by","gr","yy","rr","yr","gr"]}"#);
+        let back: NazopuyoInfo = serde_json::from_str(&info_json).unwrap();
+        assert_eq!(back.next.pairs(), info.next.pairs());
+        assert_eq!(back.next.len(), info.next.len());
+        assert_eq!(back.rules, Rules::TSU);
+
+        let info = info.rules(Rules::TSU.pop_count(3));
+        let back: NazopuyoInfo = serde_json::from_str(&serde_json::to_string(&info).unwrap()).unwrap();
+        assert_eq!(back.rules, info.rules);
+    }
+
+    #[test]
+    fn solve_result() {
+        let solved = SolveResult::Solved(NaiveField::new());
+        let json = serde_json::to_string(&solved).unwrap();
+        assert!(json.starts_with(r#"{"status":"solved","field":{"rows":["#));
+        assert_eq!(serde_json::from_str::<SolveResult<NaiveField>>(&json).unwrap(), solved);
+
+        let gave_up = SolveResult::<NaiveField>::GaveUp(GiveUpReason::NodeLimit);
+        let json = serde_json::to_string(&gave_up).unwrap();
+        assert_eq!(json, r#"{"status":"gave_up","reason":"node_limit"}"#);
+        assert_eq!(serde_json::from_str::<SolveResult<NaiveField>>(&json).unwrap(), gave_up);
+
+        let json = serde_json::to_string(&SolveResult::<NaiveField>::NoSolution).unwrap();
+        assert_eq!(json, r#"{"status":"no_solution"}"#);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(serde_json::from_str::<NaiveField>(r#"{"rows":["......"]}"#).is_err());
+        assert!(serde_json::from_str::<NaiveNextPuyo>(r#"["rb","r"]"#).is_err());
+        assert!(serde_json::from_str::<NaiveNextPuyo>(r#"["rb","r@"]"#).is_err());
+    }
+}
@@ -0,0 +1,233 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::progress::{ProgressObserver, Stats, DEFAULT_PROGRESS_INTERVAL};
+
+/// 外から探索を止めるためのトークン
+/// clone したものは全部同じフラグを見る
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// 探索の打ち切り条件と途中経過の報告先
+/// 何も指定しなければ最後まで黙って探索する
+#[derive(Clone, Default)]
+pub struct SolveOptions {
+    pub timeout: Option<Duration>,
+    pub max_nodes: Option<u64>,
+    pub cancel: Option<CancelToken>,
+    pub progress: Option<Arc<dyn ProgressObserver>>,
+    /// 何ノードごとに報告するか (0 なら既定値)
+    pub progress_interval: u64,
+}
+
+impl fmt::Debug for SolveOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SolveOptions")
+            .field("timeout", &self.timeout)
+            .field("max_nodes", &self.max_nodes)
+            .field("cancel", &self.cancel)
+            .field("progress", &self.progress.is_some())
+            .field("progress_interval", &self.progress_interval)
+            .finish()
+    }
+}
+
+impl SolveOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn max_nodes(mut self, max_nodes: u64) -> Self {
+        self.max_nodes = Some(max_nodes);
+        self
+    }
+
+    pub fn cancel(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    pub fn progress(mut self, observer: Arc<dyn ProgressObserver>, interval: u64) -> Self {
+        self.progress = Some(observer);
+        self.progress_interval = interval;
+        self
+    }
+}
+
+/// 探索を諦めた理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum GiveUpReason {
+    Timeout,
+    NodeLimit,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolveResult<F> {
+    /// 解が見つかった (連鎖させる直前の盤面)
+    Solved(F),
+    /// 最後まで探索したが解が無い
+    NoSolution,
+    /// 途中で打ち切った
+    GaveUp(GiveUpReason),
+}
+
+impl<F> SolveResult<F> {
+    pub fn is_solved(&self) -> bool {
+        matches!(self, SolveResult::Solved(_))
+    }
+
+    pub fn solution(self) -> Option<F> {
+        match self {
+            SolveResult::Solved(field) => Some(field),
+            _ => None,
+        }
+    }
+}
+
+/// 時計を見る間隔 (ノード数)
+const CLOCK_INTERVAL: u64 = 256;
+
+/// 探索中に打ち切り条件を確認し、途中経過を報告する
+pub(crate) struct Budget {
+    deadline: Option<Instant>,
+    max_nodes: Option<u64>,
+    cancel: Option<CancelToken>,
+    observer: Option<Arc<dyn ProgressObserver>>,
+    interval: u64,
+    stats: Stats,
+}
+
+impl Budget {
+    /// max_depth はツモの数
+    pub(crate) fn new(options: &SolveOptions, max_depth: usize) -> Self {
+        Self {
+            deadline: options.timeout.map(|t| Instant::now() + t),
+            max_nodes: options.max_nodes,
+            cancel: options.cancel.clone(),
+            observer: options.progress.clone(),
+            interval: match options.progress_interval {
+                0 => DEFAULT_PROGRESS_INTERVAL,
+                n => n,
+            },
+            stats: Stats::new(max_depth),
+        }
+    }
+
+    /// depth のノードを一つ数えて、打ち切るべきなら理由を返す
+    pub(crate) fn visit(&self, depth: u32) -> Option<GiveUpReason> {
+        let nodes = self.stats.visit(depth);
+        if let Some(observer) = &self.observer {
+            if nodes.is_multiple_of(self.interval) {
+                observer.on_progress(&self.stats.snapshot(false));
+            }
+        }
+        if let Some(token) = &self.cancel {
+            if token.is_cancelled() {
+                return Some(GiveUpReason::Cancelled);
+            }
+        }
+        if let Some(max_nodes) = self.max_nodes {
+            if nodes > max_nodes {
+                return Some(GiveUpReason::NodeLimit);
+            }
+        }
+        if let Some(deadline) = self.deadline {
+            if nodes % CLOCK_INTERVAL == 1 && Instant::now() >= deadline {
+                return Some(GiveUpReason::Timeout);
+            }
+        }
+        None
+    }
+
+    /// これまでに訪れたノード数
+    pub(crate) fn nodes(&self) -> u64 {
+        self.stats.nodes()
+    }
+
+    /// 置換表を引いた結果を数える
+    pub(crate) fn lookup(&self, hit: bool) {
+        self.stats.lookup(hit);
+    }
+
+    pub(crate) fn set_first_ply_total(&self, total: u64) {
+        self.stats.set_first_ply_total(total);
+    }
+
+    /// 初手を 1 つ調べ終わった (再帰で探索するとき)
+    pub(crate) fn first_ply_done(&self) {
+        self.stats.first_ply_done();
+    }
+
+    /// 初手を 1 つ探索に積んで、その番号を返す (スタックで探索するとき)
+    pub(crate) fn open_first_ply(&self) -> usize {
+        self.stats.open_first_ply()
+    }
+
+    /// branch 番の初手の下のノードを 1 つ調べ終わり、その子を children 個積んだ
+    /// 根 (branch が None) なら何もしない
+    pub(crate) fn expanded(&self, branch: Option<usize>, children: u64) {
+        if let Some(branch) = branch {
+            self.stats.expanded(branch, children);
+        }
+    }
+
+    /// 探索が終わったら最後に一度だけ呼ぶ
+    pub(crate) fn finish(&self) {
+        if let Some(observer) = &self.observer {
+            observer.on_progress(&self.stats.snapshot(true));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn node_limit() {
+        let budget = Budget::new(&SolveOptions::new().max_nodes(3), 0);
+        assert_eq!(budget.visit(0), None);
+        assert_eq!(budget.visit(0), None);
+        assert_eq!(budget.visit(0), None);
+        assert_eq!(budget.visit(0), Some(GiveUpReason::NodeLimit));
+    }
+
+    #[test]
+    fn cancel_is_shared_between_clones() {
+        let token = CancelToken::new();
+        let budget = Budget::new(&SolveOptions::new().cancel(token.clone()), 0);
+        assert_eq!(budget.visit(0), None);
+        token.cancel();
+        assert_eq!(budget.visit(0), Some(GiveUpReason::Cancelled));
+    }
+
+    #[test]
+    fn timeout() {
+        let budget = Budget::new(&SolveOptions::new().timeout(Duration::ZERO), 0);
+        assert_eq!(budget.visit(0), Some(GiveUpReason::Timeout));
+    }
+}
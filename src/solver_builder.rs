@@ -0,0 +1,331 @@
+//! ソルバーを外から作るためのビルダー
+//!
+//! ```
+//! use rust_nazopuyo_solver::cell::Cell;
+//! use rust_nazopuyo_solver::field::Field;
+//! use rust_nazopuyo_solver::naive_field::NaiveField;
+//! use rust_nazopuyo_solver::solver_builder::SolverBuilder;
+//!
+//! let mut field = NaiveField::new();
+//! field.set(12, 0, Cell::RED);
+//! field.set(12, 1, Cell::RED);
+//! field.set(12, 2, Cell::RED);
+//! let mut solver = SolverBuilder::new()
+//!     .field(field)
+//!     .next(&[[2, 3]])
+//!     .chain(1)
+//!     .build()
+//!     .unwrap();
+//! assert!(solver.solve().is_some());
+//! ```
+
+use std::fmt::{self, Display};
+use std::hash::Hash;
+
+use crate::field::{self, Field};
+use crate::naive_next_puyo::{NaiveNextPuyo, NextError};
+use crate::nazopuyo_info::NazopuyoInfo;
+use crate::nazopuyo_solver::Solver;
+use crate::none_recursive_solver::NoneRecursiveSolver;
+use crate::pruning::Pruner;
+use crate::rules::Rules;
+use crate::solve_options::SolveOptions;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    MissingField,
+    MissingNext,
+    MissingChain,
+    /// ネクストが空
+    EmptyNext,
+    /// ネクストが 10 組より多い
+    TooManyPairs(usize),
+    /// ネクストに色ぷよ (2-6) でないものがある
+    BadNextColor { pair: usize, color: u8 },
+    /// 下が空いているのに浮いているぷよがある
+    FloatingPuyo { y: usize, x: usize },
+    /// 盤面がはじめから消える
+    AlreadyChaining,
+    /// 盤面がはじめから窒息している
+    Dead,
+    /// 連鎖数が 0
+    ZeroChain,
+    /// 置いた手数がネクストより多い
+    DepthOutOfRange(u32),
+    /// ルールが盤面に合わない
+    InvalidRules(&'static str),
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::MissingField => write!(f, "no field given"),
+            BuildError::MissingNext => write!(f, "no next queue given"),
+            BuildError::MissingChain => write!(f, "no chain condition given"),
+            BuildError::EmptyNext => write!(f, "next queue is empty"),
+            BuildError::TooManyPairs(n) => write!(f, "{} pairs, expected at most 10", n),
+            BuildError::BadNextColor { pair, color } => {
+                write!(f, "pair {} has color code {}, expected 2-6", pair + 1, color)
+            }
+            BuildError::FloatingPuyo { y, x } => write!(f, "puyo at ({}, {}) is floating", y, x),
+            BuildError::AlreadyChaining => write!(f, "field already has a group that pops"),
+            BuildError::Dead => write!(f, "field is already dead"),
+            BuildError::ZeroChain => write!(f, "chain condition must be at least 1"),
+            BuildError::DepthOutOfRange(d) => write!(f, "start depth {} is past the end of the next queue", d),
+            BuildError::InvalidRules(why) => write!(f, "invalid rules: {}", why),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// 盤面 + ネクスト + 条件 + 打ち切り条件 からソルバーを作る
+pub struct SolverBuilder<F: Field> {
+    field: Option<F>,
+    next: Option<Vec<[u8; 2]>>,
+    chain: Option<u32>,
+    depth: u32,
+    rules: Rules,
+    options: SolveOptions,
+    pruners: Vec<Box<dyn Pruner<F>>>,
+}
+
+impl<F> Default for SolverBuilder<F>
+where
+    F: Field + Clone + Eq + Hash + Display + Send + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Field> fmt::Debug for SolverBuilder<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SolverBuilder")
+            .field("next", &self.next)
+            .field("chain", &self.chain)
+            .field("depth", &self.depth)
+            .field("rules", &self.rules)
+            .field("options", &self.options)
+            .field("pruners", &self.pruners)
+            .finish()
+    }
+}
+
+impl<F> SolverBuilder<F>
+where
+    F: Field + Clone + Eq + Hash + Display + Send + 'static,
+{
+    pub fn new() -> Self {
+        Self {
+            field: None,
+            next: None,
+            chain: None,
+            depth: 0,
+            rules: Rules::default(),
+            options: SolveOptions::default(),
+            pruners: vec![],
+        }
+    }
+
+    pub fn field(mut self, field: F) -> Self {
+        self.field = Some(field);
+        self
+    }
+
+    /// 1 組目の [0] が軸ぷよ
+    pub fn next(mut self, pairs: &[[u8; 2]]) -> Self {
+        self.next = Some(pairs.to_vec());
+        self
+    }
+
+    /// chain 連鎖する
+    pub fn chain(mut self, chain: u32) -> Self {
+        self.chain = Some(chain);
+        self
+    }
+
+    /// パースした問題をそのまま使う
+    pub fn info(self, info: &NazopuyoInfo) -> Self {
+        let pairs = info.next.pairs().to_vec();
+        self.next(&pairs).chain(info.chain).rules(info.rules)
+    }
+
+    /// field が depth 手置いたあとの盤面のとき
+    pub fn depth(mut self, depth: u32) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// 既定はぷよぷよ通
+    pub fn rules(mut self, rules: Rules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    pub fn options(mut self, options: SolveOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn pruner<P: Pruner<F> + 'static>(mut self, pruner: P) -> Self {
+        self.pruners.push(Box::new(pruner));
+        self
+    }
+
+    /// 再帰版のソルバー
+    pub fn build(self) -> Result<Solver<F>, BuildError> {
+        let (field, info) = self.validate()?;
+        let mut solver = Solver::from_position(field, info, self.depth);
+        solver.set_options(self.options);
+        for p in self.pruners {
+            solver.add_boxed_pruner(p);
+        }
+        Ok(solver)
+    }
+
+    /// スタックを使う非再帰版のソルバー
+    pub fn build_stack(self) -> Result<NoneRecursiveSolver<F>, BuildError> {
+        let (field, info) = self.validate()?;
+        let mut solver = NoneRecursiveSolver::from_position(field, info, self.depth);
+        solver.set_options(self.options);
+        for p in self.pruners {
+            solver.add_boxed_pruner(p);
+        }
+        Ok(solver)
+    }
+
+    fn validate(&self) -> Result<(F, NazopuyoInfo), BuildError> {
+        let field = self.field.clone().ok_or(BuildError::MissingField)?;
+        let pairs = self.next.clone().ok_or(BuildError::MissingNext)?;
+        let chain = self.chain.ok_or(BuildError::MissingChain)?;
+
+        if pairs.is_empty() {
+            return Err(BuildError::EmptyNext);
+        }
+        let next = NaiveNextPuyo::from_vec(pairs.clone()).map_err(|e| match e {
+            NextError::TooManyPairs(n) => BuildError::TooManyPairs(n),
+            NextError::BadColor { pair, code } => BuildError::BadNextColor { pair, color: code },
+        })?;
+        if chain == 0 {
+            return Err(BuildError::ZeroChain);
+        }
+        if self.depth as usize > pairs.len() {
+            return Err(BuildError::DepthOutOfRange(self.depth));
+        }
+        let rules = self.rules;
+        if rules.pop_count == 0 {
+            return Err(BuildError::InvalidRules("pop count must be at least 1"));
+        }
+        if rules.hidden_rows >= F::HEIGHT {
+            return Err(BuildError::InvalidRules("hidden rows cover the whole field"));
+        }
+        if rules.death_cell.0 >= F::HEIGHT || rules.death_cell.1 >= F::WIDTH {
+            return Err(BuildError::InvalidRules("death cell is outside the field"));
+        }
+
+        for x in 0..F::WIDTH {
+            let mut empty_below = false;
+            for y in (0..F::HEIGHT).rev() {
+                if field.get(y, x).is_empty() {
+                    empty_below = true;
+                } else if empty_below {
+                    return Err(BuildError::FloatingPuyo { y, x });
+                }
+            }
+        }
+        if field::has_chain_with(&field, &rules) {
+            return Err(BuildError::AlreadyChaining);
+        }
+        if !field::is_alive_with(&field, &rules) {
+            return Err(BuildError::Dead);
+        }
+
+        let info = NazopuyoInfo::new(chain, next).rules(rules);
+        Ok((field, info))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cell::Cell;
+    use crate::field_naive_bit::FieldNaiveBit;
+    use crate::naive_field::NaiveField;
+    use crate::pruning::ChainUpperBound;
+    use crate::solve_options::{GiveUpReason, SolveResult};
+
+    fn three_reds() -> NaiveField {
+        let mut field = NaiveField::new();
+        field.set(12, 0, Cell::RED);
+        field.set(12, 1, Cell::RED);
+        field.set(12, 2, Cell::RED);
+        field
+    }
+
+    #[test]
+    fn builds_both_solvers() {
+        let builder = || SolverBuilder::new().field(three_reds()).next(&[[2, 3]]).chain(1);
+        assert!(builder().build().unwrap().solve().is_some());
+        assert!(builder().pruner(ChainUpperBound).build_stack().unwrap().solve().is_some());
+
+        // 打ち切り条件は solve でも効く
+        let mut solver = builder().options(SolveOptions::new().max_nodes(1)).build().unwrap();
+        assert_eq!(solver.solve(), None);
+        assert_eq!(
+            solver.solve_with(&SolveOptions::new().max_nodes(1)),
+            SolveResult::GaveUp(GiveUpReason::NodeLimit)
+        );
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        let err = |b: SolverBuilder<NaiveField>| b.build().unwrap_err();
+        let ok = || SolverBuilder::new().field(three_reds()).next(&[[2, 3]]).chain(1);
+
+        assert_eq!(err(SolverBuilder::new().next(&[[2, 3]]).chain(1)), BuildError::MissingField);
+        assert_eq!(err(ok().next(&[])), BuildError::EmptyNext);
+        assert_eq!(err(ok().next(&[[2, 3]; 11])), BuildError::TooManyPairs(11));
+        assert_eq!(err(ok().next(&[[2, 3], [1, 3]])), BuildError::BadNextColor { pair: 1, color: 1 });
+        assert_eq!(err(ok().chain(0)), BuildError::ZeroChain);
+        assert_eq!(err(ok().depth(2)), BuildError::DepthOutOfRange(2));
+
+        let mut chaining = three_reds();
+        chaining.set(11, 0, Cell::RED);
+        assert_eq!(err(ok().field(chaining)), BuildError::AlreadyChaining);
+
+        let mut floating = three_reds();
+        floating.set(5, 4, Cell::BLUE);
+        assert_eq!(err(ok().field(floating)), BuildError::FloatingPuyo { y: 5, x: 4 });
+
+        // 固ぷよは置いてよい
+        let mut hard = FieldNaiveBit::new();
+        hard.set(12, 3, Cell::Hard);
+        assert!(SolverBuilder::new().field(hard).next(&[[2, 3]]).chain(1).build().is_ok());
+    }
+
+    #[test]
+    fn rules() {
+        let err = |b: SolverBuilder<NaiveField>| b.build().unwrap_err();
+        let ok = || SolverBuilder::new().field(three_reds()).next(&[[2, 3]]).chain(1);
+
+        // 3 個で消えるなら赤 3 個がはじめから消える
+        assert_eq!(err(ok().rules(Rules::TSU.pop_count(3))), BuildError::AlreadyChaining);
+        assert!(matches!(err(ok().rules(Rules::TSU.pop_count(0))), BuildError::InvalidRules(_)));
+        assert!(matches!(err(ok().rules(Rules::TSU.hidden_rows(13))), BuildError::InvalidRules(_)));
+        assert!(matches!(err(ok().rules(Rules::TSU.death_cell(1, 6))), BuildError::InvalidRules(_)));
+        // 窒息点が埋まっている
+        assert_eq!(err(ok().rules(Rules::TSU.death_cell(12, 0))), BuildError::Dead);
+
+        // 赤 2 個 + 赤を 1 個置けば 3 個で消える
+        let mut two = NaiveField::new();
+        two.set(12, 0, Cell::RED);
+        two.set(12, 1, Cell::RED);
+        let builder = || SolverBuilder::new().field(two.clone()).next(&[[2, 3]]).chain(1);
+        assert!(builder().build().unwrap().solve().is_none());
+        let mut solver = builder().rules(Rules::TSU.pop_count(3)).build_stack().unwrap();
+        let res = solver.solve().unwrap();
+        assert_eq!(field::chain_with(&mut res.clone(), &Rules::TSU.pop_count(3)), 1);
+        assert_eq!(field::chain(&mut res.clone()), 0);
+    }
+}
@@ -0,0 +1,298 @@
+//! SVG の画像を書き出す
+//!
+//! 盤面 1 枚 (右にネクスト、上に次の手の矢印) と、手順を 1 手ずつ横に並べた図が書ける
+//! 文字列を組み立てるだけなので、外のライブラリは使わない
+
+use std::fmt::Write;
+
+use crate::cell::{Cell, Color};
+use crate::field::Field;
+use crate::naive_next_puyo::NaiveNextPuyo;
+use crate::nazopuyo_info::NazopuyoInfo;
+use crate::placement::{play, IllegalMove, Placement, Rotation};
+use crate::render::placed_cells;
+
+/// 1 マスの大きさ (px)
+const CELL: usize = 32;
+/// 盤面の上の矢印を描く場所の高さ
+const TOP: usize = 2 * CELL;
+/// 盤面の下の見出しの高さ
+const BOTTOM: usize = CELL;
+/// 並べるときの間隔
+const GAP: usize = CELL / 2;
+
+fn fill(color: Color) -> &'static str {
+    match color {
+        Color::Red => "#e53935",
+        Color::Blue => "#1e88e5",
+        Color::Green => "#43a047",
+        Color::Yellow => "#fdd835",
+        Color::Purple => "#8e24aa",
+    }
+}
+
+/// 1 個のぷよ。(cx, cy) が中心、r が半径
+fn puyo(out: &mut String, cell: Cell, cx: usize, cy: usize, r: usize, highlight: bool) {
+    let stroke = if highlight {
+        r##" stroke="#000" stroke-width="3""##
+    } else {
+        ""
+    };
+    match cell {
+        Cell::Empty => {}
+        Cell::Color(color) => {
+            let _ = writeln!(out, r#"<circle cx="{}" cy="{}" r="{}" fill="{}"{}/>"#, cx, cy, r, fill(color), stroke);
+        }
+        Cell::Ojama => {
+            let _ = writeln!(out, r##"<circle cx="{}" cy="{}" r="{}" fill="#bdbdbd"{}/>"##, cx, cy, r, stroke);
+        }
+        Cell::Hard => {
+            let _ = writeln!(
+                out,
+                r##"<rect x="{}" y="{}" width="{}" height="{}" rx="4" fill="#757575"{}/>"##,
+                cx - r,
+                cy - r,
+                2 * r,
+                2 * r,
+                stroke
+            );
+        }
+    }
+}
+
+/// 盤面 1 枚の描き方
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SvgWriter {
+    /// 右に並べるネクスト ([軸ぷよ, 子ぷよ])
+    next: Vec<[Cell; 2]>,
+    /// 上に描く次の手
+    arrow: Option<(Placement, [Cell; 2])>,
+    /// 縁取りするマス (段, 列)
+    highlight: Vec<(usize, usize)>,
+    caption: Option<String>,
+}
+
+impl SvgWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// from 組目 (0 始まり) から後ろのネクストを右に並べる
+    pub fn next(mut self, next: &NaiveNextPuyo, from: usize) -> Self {
+        self.next = (from..next.len()).map(|i| next.pair(i)).collect();
+        self
+    }
+
+    /// pair をどこに置くかを盤面の上に描く
+    pub fn arrow(mut self, placement: Placement, pair: [Cell; 2]) -> Self {
+        self.arrow = Some((placement, pair));
+        self
+    }
+
+    pub fn highlight(mut self, cells: impl IntoIterator<Item = (usize, usize)>) -> Self {
+        self.highlight = cells.into_iter().collect();
+        self
+    }
+
+    /// 盤面の下に書く文字 (XML の特殊文字はエスケープする)
+    pub fn caption(mut self, caption: &str) -> Self {
+        self.caption = Some(caption.to_string());
+        self
+    }
+
+    /// 1 枚の SVG にする
+    pub fn write<F: Field>(&self, field: &F) -> String {
+        let mut out = header(panel_width::<F>(), panel_height::<F>());
+        self.panel(&mut out, field, 0);
+        out.push_str("</svg>\n");
+        out
+    }
+
+    /// x0 から右に 1 枚分描く
+    fn panel<F: Field>(&self, out: &mut String, field: &F, x0: usize) {
+        let _ = writeln!(out, r#"<g transform="translate({},0)">"#, x0);
+        let (w, h) = (F::WIDTH * CELL, F::HEIGHT * CELL);
+        let _ = writeln!(out, r##"<rect x="0" y="{}" width="{}" height="{}" fill="#fafafa" stroke="#424242"/>"##, TOP, w, h);
+        // 見えない段
+        let _ = writeln!(out, r##"<rect x="0" y="{}" width="{}" height="{}" fill="#e0e0e0"/>"##, TOP, w, CELL);
+
+        let r = CELL / 2 - 2;
+        for y in 0..F::HEIGHT {
+            for x in 0..F::WIDTH {
+                let (cx, cy) = (x * CELL + CELL / 2, TOP + y * CELL + CELL / 2);
+                puyo(out, field.get(y, x), cx, cy, r, self.highlight.contains(&(y, x)));
+            }
+        }
+
+        // ネクストは半分の大きさで、子ぷよを上にして縦に並べる
+        let small = CELL / 4 - 1;
+        for (i, pair) in self.next.iter().enumerate() {
+            let cx = w + CELL / 2;
+            let top = TOP + i * (CELL + CELL / 4);
+            puyo(out, pair[1], cx, top + CELL / 4, small, false);
+            puyo(out, pair[0], cx, top + 3 * CELL / 4, small, false);
+        }
+
+        if let Some((placement, pair)) = self.arrow {
+            self.draw_arrow::<F>(out, placement, pair);
+        }
+
+        if let Some(caption) = &self.caption {
+            let _ = writeln!(
+                out,
+                r#"<text x="{}" y="{}" font-family="sans-serif" font-size="{}" text-anchor="middle">{}</text>"#,
+                w / 2,
+                TOP + h + BOTTOM - 8,
+                CELL / 2,
+                escape(caption)
+            );
+        }
+        out.push_str("</g>\n");
+    }
+
+    /// 上の余白に置くツモを向きどおりに描いて、軸ぷよの列に下向きの矢印を付ける
+    fn draw_arrow<F: Field>(&self, out: &mut String, placement: Placement, pair: [Cell; 2]) {
+        let child_x = match placement.child_x_in(F::WIDTH) {
+            Some(c) if placement.x < F::WIDTH => c,
+            _ => return,
+        };
+        let small = CELL / 4 - 1;
+        let center = |x: usize| x * CELL + CELL / 2;
+        let (axis_y, child_y) = match placement.rotation {
+            Rotation::Up => (3 * CELL / 4, CELL / 4),
+            Rotation::Down => (CELL / 4, 3 * CELL / 4),
+            Rotation::Right | Rotation::Left => (CELL / 2, CELL / 2),
+        };
+        puyo(out, pair[0], center(placement.x), axis_y, small, false);
+        puyo(out, pair[1], center(child_x), child_y, small, false);
+
+        let cx = (center(placement.x) + center(child_x)) / 2;
+        let _ = writeln!(
+            out,
+            r##"<line x1="{cx}" y1="{}" x2="{cx}" y2="{}" stroke="#424242" stroke-width="2"/>"##,
+            CELL + 2,
+            TOP - 8,
+        );
+        let _ = writeln!(
+            out,
+            r##"<polygon points="{},{} {},{} {},{}" fill="#424242"/>"##,
+            cx - 5,
+            TOP - 10,
+            cx + 5,
+            TOP - 10,
+            cx,
+            TOP - 2
+        );
+    }
+}
+
+fn panel_width<F: Field>() -> usize {
+    // 右のネクストの分 1 マス足す
+    (F::WIDTH + 1) * CELL
+}
+
+fn panel_height<F: Field>() -> usize {
+    TOP + F::HEIGHT * CELL + BOTTOM
+}
+
+fn header(width: usize, height: usize) -> String {
+    format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+        w = width,
+        h = height
+    ) + "\n"
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// 手順を 1 手ずつ横に並べた図
+/// i 枚目は i 手目を置く前の盤面と、その手の矢印。最後の 1 枚は全部置いたあと (発火前) の盤面
+/// 置いたばかりのぷよは縁取りする。置けない手があれば (何手目か (0 始まり), 理由)
+pub fn solution_strip<F: Field + Clone>(
+    field: &F,
+    info: &NazopuyoInfo,
+    placements: &[Placement],
+) -> Result<String, (usize, IllegalMove)> {
+    let panels = placements.len() + 1;
+    let width = panels * panel_width::<F>() + (panels - 1) * GAP;
+    let mut out = header(width, panel_height::<F>());
+
+    let mut current = field.clone();
+    let mut placed = vec![];
+    for (step, &placement) in placements.iter().enumerate() {
+        let writer = SvgWriter::new()
+            .next(&info.next, step)
+            .arrow(placement, info.next.pair(step))
+            .highlight(placed.iter().copied())
+            .caption(&format!("{}: {}", step + 1, placement));
+        writer.panel(&mut out, &current, step * (panel_width::<F>() + GAP));
+
+        let before = current.clone();
+        play(&mut current, info, step, placement).map_err(|reason| (step, reason))?;
+        placed = placed_cells(&before, &current);
+    }
+    let writer = SvgWriter::new().highlight(placed).caption("fire");
+    writer.panel(&mut out, &current, placements.len() * (panel_width::<F>() + GAP));
+
+    out.push_str("</svg>\n");
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::naive_field::NaiveField;
+    use crate::placement::parse_placements;
+    use crate::puzzle_text::parse_puzzle;
+
+    /// 左端の 1 列目は固ぷよの上。2 列目の青 2 個に 3 列目で青を足すと消える
+    fn puzzle(next: &str) -> (NaiveField, NazopuyoInfo) {
+        let text = format!("{}.b....\nhbg@..\nnext: {}\nchain: 1\n", "......\n".repeat(11), next);
+        parse_puzzle(&text).unwrap()
+    }
+
+    #[test]
+    fn single_board() {
+        let (field, info) = puzzle("gy bb");
+        let svg = SvgWriter::new()
+            .next(&info.next, 0)
+            .arrow(Placement::new(0, Rotation::Down), info.next.pair(0))
+            .caption("a < b")
+            .write(&field);
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="224" height="512""#));
+        assert!(svg.ends_with("</svg>\n"));
+        // 盤面の色ぷよ 3 個とおじゃま + ネクスト 4 個 + 矢印のツモ 2 個。固ぷよは四角
+        assert_eq!(svg.matches("<circle").count(), 10);
+        assert!(svg.contains(r##"<rect x="2" y="450" width="28" height="28" rx="4" fill="#757575"/>"##));
+        // 下向きなら軸ぷよが上、子ぷよが下
+        assert!(svg.contains(r##"<circle cx="16" cy="8" r="7" fill="#43a047"/>"##));
+        assert!(svg.contains(r##"<circle cx="16" cy="24" r="7" fill="#fdd835"/>"##));
+        assert!(svg.contains(r##"<polygon points="11,54 21,54 16,62" fill="#424242"/>"##));
+        assert!(svg.contains(">a &lt; b</text>"));
+
+        // 子ぷよが盤面の外に出る手は描かない
+        let svg = SvgWriter::new()
+            .arrow(Placement::new(0, Rotation::Left), info.next.pair(0))
+            .write(&field);
+        assert_eq!(svg.matches("<polygon").count(), 0);
+    }
+
+    #[test]
+    fn strip() {
+        let (field, info) = puzzle("gy bb");
+        let placements = parse_placements("1d 3u").unwrap();
+        let svg = solution_strip(&field, &info, &placements).unwrap();
+        assert_eq!(svg.matches("<g transform").count(), 3);
+        assert_eq!(svg.matches("<polygon").count(), 2);
+        // 2 枚目で 1 手目の 2 個、最後の 1 枚で置いたばかりの青 2 個を縁取りする
+        assert_eq!(svg.matches(r##"stroke="#000""##).count(), 2 + 2);
+        assert!(svg.contains(">1: 1d</text>"));
+        assert!(svg.contains(">fire</text>"));
+
+        // 青から置くと 1 手目で消えてしまう
+        let (_, early) = puzzle("bb gy");
+        assert_eq!(solution_strip(&field, &early, &placements), Err((0, IllegalMove::EarlyChain)));
+    }
+}
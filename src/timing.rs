@@ -0,0 +1,181 @@
+//! 連鎖や手順にかかるフレーム数 (60 フレームで 1 秒)
+//!
+//! 連鎖は 1 連鎖ごとに 消えるアニメーション + 落ちた段数分 + 着地 で数える
+//! 置くのは 1 組ごとに一定で、ちぎったときだけ浮いたほうが落ちる分を足す
+
+use std::fmt;
+
+use crate::chain_frames::{chain_frames, FrameKind};
+use crate::field::Field;
+use crate::nazopuyo_info::NazopuyoInfo;
+use crate::placement::{drop_row, play, IllegalMove, Placement};
+use crate::rules::Rules;
+
+/// 1 つ 1 つの動きにかかるフレーム数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrameCosts {
+    /// ツモが出てきてから置くまで
+    pub placement: u32,
+    /// 消えるアニメーション
+    pub pop: u32,
+    /// 1 段落ちる
+    pub fall_per_row: u32,
+    /// 落ちたぷよが着地して次が動き出すまで
+    pub landing: u32,
+}
+
+impl FrameCosts {
+    /// ぷよぷよ通のだいたいの値
+    pub const TSU: FrameCosts = FrameCosts {
+        placement: 20,
+        pop: 50,
+        fall_per_row: 2,
+        landing: 16,
+    };
+}
+
+impl Default for FrameCosts {
+    fn default() -> Self {
+        FrameCosts::TSU
+    }
+}
+
+/// 手順全体のフレーム数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Timing {
+    /// 全部置くまで
+    pub placement: u32,
+    /// 最後に置いてから連鎖が終わるまで
+    pub chain: u32,
+}
+
+impl Timing {
+    pub fn total(&self) -> u32 {
+        self.placement + self.chain
+    }
+
+    pub fn seconds(&self) -> f64 {
+        self.total() as f64 / 60.0
+    }
+}
+
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} frames ({:.2}s): placement {}, chain {}",
+            self.total(),
+            self.seconds(),
+            self.placement,
+            self.chain
+        )
+    }
+}
+
+/// 落ちる動き 1 回分。落ちなければ 0
+pub(crate) fn fall_frames(distance: usize, costs: &FrameCosts) -> u32 {
+    if distance == 0 {
+        0
+    } else {
+        distance as u32 * costs.fall_per_row + costs.landing
+    }
+}
+
+/// field を rules で連鎖させ終わるまでのフレーム数。消えなければ 0
+pub fn chain_timing<F: Field + Clone>(field: &F, rules: &Rules, costs: &FrameCosts) -> u32 {
+    chain_frames(field, rules)
+        .map(|frame| match frame.kind {
+            FrameKind::Placed => 0,
+            FrameKind::Popped { .. } => costs.pop,
+            FrameKind::Fell { distance, .. } => fall_frames(distance, costs),
+        })
+        .sum()
+}
+
+/// field に 1 組置くフレーム数。ちぎりなら浮いたほうが落ちる分を足す
+/// 置けるかどうかは見ない
+pub fn placement_timing<F: Field>(field: &F, placement: Placement, costs: &FrameCosts) -> u32 {
+    let split = match placement.child_x_in(F::WIDTH) {
+        Some(child_x) if child_x != placement.x => {
+            match (drop_row(field, placement.x), drop_row(field, child_x)) {
+                (Some(a), Some(b)) => a.abs_diff(b),
+                _ => 0,
+            }
+        }
+        _ => 0,
+    };
+    costs.placement + fall_frames(split, costs)
+}
+
+/// 手順を最初から置いて、最後の連鎖が終わるまでのフレーム数
+/// 置けない手があれば (何手目か (0 始まり), 理由)
+pub fn sequence_timing<F: Field + Clone>(
+    field: &F,
+    info: &NazopuyoInfo,
+    placements: &[Placement],
+    costs: &FrameCosts,
+) -> Result<Timing, (usize, IllegalMove)> {
+    let mut field = field.clone();
+    let mut timing = Timing::default();
+    for (step, &placement) in placements.iter().enumerate() {
+        timing.placement += placement_timing(&field, placement, costs);
+        play(&mut field, info, step, placement).map_err(|reason| (step, reason))?;
+    }
+    timing.chain = chain_timing(&field, &info.rules, costs);
+    Ok(timing)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::naive_field::NaiveField;
+    use crate::placement::parse_placements;
+    use crate::puzzle_text::parse_puzzle;
+
+    fn puzzle(rows: &str, next: &str, chain: u32) -> (NaiveField, NazopuyoInfo) {
+        let rows: Vec<&str> = rows.split_whitespace().collect();
+        let mut text = "......\n".repeat(13 - rows.len());
+        for row in rows {
+            text.push_str(row);
+            text.push('\n');
+        }
+        text.push_str(&format!("next: {}\nchain: {}\n", next, chain));
+        parse_puzzle(&text).unwrap()
+    }
+
+    #[test]
+    fn chain_frames_count() {
+        let costs = FrameCosts::TSU;
+        // 1 連鎖で上に何も無ければ 消えるだけ
+        let (field, _) = puzzle("rrrr..", "rr", 1);
+        assert_eq!(chain_timing(&field, &Rules::TSU, &costs), costs.pop);
+        // 2 連鎖。1 連鎖目のあと青が 3 段落ちる、2 連鎖目のあとは何も落ちない
+        let (field, _) = puzzle("b..... r..... rbb... rrb...", "rr", 2);
+        assert_eq!(
+            chain_timing(&field, &Rules::TSU, &costs),
+            costs.pop * 2 + 3 * costs.fall_per_row + costs.landing
+        );
+        assert_eq!(chain_timing(&NaiveField::new(), &Rules::TSU, &costs), 0);
+    }
+
+    #[test]
+    fn sequence() {
+        let costs = FrameCosts::TSU;
+        let rows = ".....g b....y r.b..g rbb..y";
+        let (field, info) = puzzle(rows, "gy rr", 2);
+        // 1 手目は 5 列目と 4 段積んだ 6 列目にちぎるので、5 列目の緑が 4 段落ちる
+        // 2 手目で赤が消え、1 列目の青が 2 段落ちて 2 連鎖
+        let placements = parse_placements("5r 2u").unwrap();
+        let timing = sequence_timing(&field, &info, &placements, &costs).unwrap();
+        assert_eq!(timing.placement, costs.placement * 2 + 4 * costs.fall_per_row + costs.landing);
+        assert_eq!(timing.chain, costs.pop * 2 + 2 * costs.fall_per_row + costs.landing);
+        assert_eq!(timing.total(), timing.placement + timing.chain);
+
+        // 順番が逆なら 1 手目で消えてしまう
+        let (_, early) = puzzle(rows, "rr gy", 2);
+        let swapped = parse_placements("2u 5r").unwrap();
+        assert_eq!(sequence_timing(&field, &early, &swapped, &costs), Err((0, IllegalMove::EarlyChain)));
+    }
+}